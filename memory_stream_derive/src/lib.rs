@@ -0,0 +1,457 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DataEnum, DeriveInput, Expr, ExprLit, Fields,
+    GenericParam, Generics, Ident, Lit, LitInt, Type,
+};
+
+// 为结构体和枚举生成 memory_stream::codec::Encode 实现。
+// 字段属性：#[bits(n)] 按 n 位写入整数或 bool，#[skip] 跳过字段，
+// #[endianness(little | big)] 临时切换字节序。
+// 容器属性：#[endianness(..)] 作用于所有字段，枚举上的 #[bits(n)] 指定标签位数。
+// 枚举的标签是变体的判别值，没有写判别值时和 Rust 一样从 0 开始依次加 1
+#[proc_macro_derive(StreamEncode, attributes(bits, skip, endianness))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// 为结构体和枚举生成 memory_stream::codec::Decode 实现，属性与 StreamEncode 相同。
+// 标记 #[skip] 的字段使用 Default::default()
+#[proc_macro_derive(StreamDecode, attributes(bits, skip, endianness))]
+pub fn derive_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct Attrs {
+    bits: Option<u32>,
+    skip: bool,
+    endianness: Option<TokenStream2>,
+}
+
+fn parse_attrs(attrs: &[Attribute]) -> syn::Result<Attrs> {
+    let mut result = Attrs::default();
+    for attr in attrs {
+        if attr.path().is_ident("bits") {
+            let lit: LitInt = attr.parse_args()?;
+            let bits = lit.base10_parse::<u32>()?;
+            if !(1..=64).contains(&bits) {
+                return Err(syn::Error::new_spanned(lit, "bits must be between 1 and 64"));
+            }
+            result.bits = Some(bits);
+        } else if attr.path().is_ident("skip") {
+            attr.meta.require_path_only()?;
+            result.skip = true;
+        } else if attr.path().is_ident("endianness") {
+            let ident: Ident = attr.parse_args()?;
+            result.endianness = Some(match ident.to_string().as_str() {
+                "little" => quote!(::memory_stream::byte_stream::Endianness::LittleEndian),
+                "big" => quote!(::memory_stream::byte_stream::Endianness::BigEndian),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "expected `little` or `big`",
+                    ))
+                }
+            });
+        }
+    }
+    Ok(result)
+}
+
+enum BitsKind {
+    Unsigned,
+    Signed,
+    Bool,
+}
+
+// #[bits(n)] 只支持固定宽度的整数和 bool
+fn bits_kind(ty: &Type, bits: u32) -> syn::Result<BitsKind> {
+    let (kind, width) = match ty {
+        Type::Path(path) if path.qself.is_none() && path.path.segments.len() == 1 => {
+            match path.path.segments[0].ident.to_string().as_str() {
+                "u8" => (BitsKind::Unsigned, 8),
+                "u16" => (BitsKind::Unsigned, 16),
+                "u32" => (BitsKind::Unsigned, 32),
+                "u64" => (BitsKind::Unsigned, 64),
+                "i8" => (BitsKind::Signed, 8),
+                "i16" => (BitsKind::Signed, 16),
+                "i32" => (BitsKind::Signed, 32),
+                "i64" => (BitsKind::Signed, 64),
+                "bool" => (BitsKind::Bool, 64),
+                _ => return Err(unsupported_bits(ty)),
+            }
+        }
+        _ => return Err(unsupported_bits(ty)),
+    };
+    if bits > width {
+        return Err(syn::Error::new_spanned(
+            ty,
+            format!("#[bits({})] is wider than the field type", bits),
+        ));
+    }
+    Ok(kind)
+}
+
+// 变体的标签：有显式判别值时使用判别值，否则是上一个标签加 1，和 Rust 的判别值规则一致。
+// 枚举上的 #[bits(n)] 必须能容纳所有标签，没有 #[bits] 时标签按 u32 写入
+fn variant_tags(data: &DataEnum, bits: Option<u32>) -> syn::Result<Vec<u64>> {
+    let max = match bits {
+        Some(bits) => u64::MAX >> (64 - bits),
+        None => u32::MAX as u64,
+    };
+    let mut tags = Vec::new();
+    let mut next = Some(0u64);
+    for variant in &data.variants {
+        let tag = match &variant.discriminant {
+            Some((_, Expr::Lit(ExprLit { lit: Lit::Int(lit), .. }))) => lit.base10_parse::<u64>()?,
+            Some((_, expr)) => {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "only non-negative integer literal discriminants are supported",
+                ))
+            }
+            None => next.ok_or_else(|| syn::Error::new_spanned(variant, "variant tag overflows u64"))?,
+        };
+        if tag > max {
+            let width = match bits {
+                Some(bits) => format!("#[bits({})]", bits),
+                None => "a u32".to_string(),
+            };
+            return Err(syn::Error::new_spanned(
+                variant,
+                format!("tag {} of variant {} does not fit in {} tag", tag, variant.ident, width),
+            ));
+        }
+        tags.push(tag);
+        next = tag.checked_add(1);
+    }
+    Ok(tags)
+}
+
+fn unsupported_bits(ty: &Type) -> syn::Error {
+    syn::Error::new_spanned(ty, "#[bits] is only supported on u8..u64, i8..i64 and bool fields")
+}
+
+// 在 body 前后切换并恢复字节序，body 为 Result 表达式
+fn with_endianness(
+    endianness: &Option<TokenStream2>,
+    stream: &Ident,
+    trait_path: &TokenStream2,
+    body: TokenStream2,
+) -> TokenStream2 {
+    match endianness {
+        Some(endianness) => quote! {{
+            let __prev = #trait_path::endianness(#stream);
+            #trait_path::set_endianness(#stream, #endianness);
+            let __result = #body;
+            #trait_path::set_endianness(#stream, __prev);
+            __result
+        }},
+        None => body,
+    }
+}
+
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
+
+// 生成的局部变量和流参数都以 __ 开头，避免和字段名冲突
+fn writer_ident() -> Ident {
+    format_ident!("__writer")
+}
+
+fn reader_ident() -> Ident {
+    format_ident!("__reader")
+}
+
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => format_ident!("__field_{}", ident),
+            None => format_ident!("__field_{}", i),
+        })
+        .collect()
+}
+
+// 匹配字段的模式，例如 { a: __field_a, b: __field_b } 或 (__field_0, __field_1)
+fn fields_pattern(fields: &Fields, bindings: &[Ident]) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote!({ #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+        Fields::Unit => quote!(),
+    }
+}
+
+fn encode_fields(fields: &Fields, bindings: &[Ident]) -> syn::Result<TokenStream2> {
+    let writer = writer_ident();
+    let trait_path = quote!(::memory_stream::stream::StreamWriter);
+    let mut statements = Vec::new();
+    for (field, binding) in fields.iter().zip(bindings) {
+        let attrs = parse_attrs(&field.attrs)?;
+        if attrs.skip {
+            statements.push(quote!(let _ = #binding;));
+            continue;
+        }
+        let body = match attrs.bits {
+            Some(bits) => match bits_kind(&field.ty, bits)? {
+                BitsKind::Signed => {
+                    quote!(#trait_path::write_signed_bits(#writer, *#binding as i64, #bits))
+                }
+                BitsKind::Unsigned | BitsKind::Bool => {
+                    quote!(#trait_path::write_bits(#writer, *#binding as u64, #bits))
+                }
+            },
+            None => quote!(::memory_stream::codec::Encode::encode(#binding, #writer)),
+        };
+        let body = with_endianness(&attrs.endianness, &writer, &trait_path, body);
+        statements.push(quote!(#body?;));
+    }
+    Ok(quote!(#(#statements)*))
+}
+
+fn decode_fields(fields: &Fields, bindings: &[Ident]) -> syn::Result<TokenStream2> {
+    let reader = reader_ident();
+    let trait_path = quote!(::memory_stream::stream::StreamReader);
+    let mut statements = Vec::new();
+    for (field, binding) in fields.iter().zip(bindings) {
+        let attrs = parse_attrs(&field.attrs)?;
+        let ty = &field.ty;
+        if attrs.skip {
+            statements.push(quote!(let #binding: #ty = ::core::default::Default::default();));
+            continue;
+        }
+        let body = match attrs.bits {
+            Some(bits) => match bits_kind(ty, bits)? {
+                BitsKind::Unsigned => {
+                    quote!(#trait_path::read_bits(#reader, #bits).map(|value| value as #ty))
+                }
+                BitsKind::Signed => {
+                    quote!(#trait_path::read_signed_bits(#reader, #bits).map(|value| value as #ty))
+                }
+                BitsKind::Bool => {
+                    quote!(#trait_path::read_bits(#reader, #bits).map(|value| value != 0))
+                }
+            },
+            None => quote!(<#ty as ::memory_stream::codec::Decode>::decode(#reader)),
+        };
+        let body = with_endianness(&attrs.endianness, &reader, &trait_path, body);
+        statements.push(quote!(let #binding: #ty = #body?;));
+    }
+    Ok(quote!(#(#statements)*))
+}
+
+// 字段编码后至少占用的位数之和，跳过的字段不占位，#[bits(n)] 字段占 n 位
+fn fields_min_bits(fields: &Fields) -> syn::Result<TokenStream2> {
+    let mut terms = Vec::new();
+    for field in fields {
+        let attrs = parse_attrs(&field.attrs)?;
+        let ty = &field.ty;
+        if attrs.skip {
+            continue;
+        }
+        terms.push(match attrs.bits {
+            Some(bits) => {
+                let bits = bits as usize;
+                quote!(#bits)
+            }
+            None => quote!(<#ty as ::memory_stream::codec::Decode>::MIN_BITS),
+        });
+    }
+    Ok(quote!(0 #(+ #terms)*))
+}
+
+fn expand_encode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let container = parse_attrs(&input.attrs)?;
+    if container.skip {
+        return Err(syn::Error::new_spanned(name, "#[skip] is only allowed on fields"));
+    }
+    let trait_path = quote!(::memory_stream::stream::StreamWriter);
+    let writer = writer_ident();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            if container.bits.is_some() {
+                return Err(syn::Error::new_spanned(name, "#[bits] on a struct is not supported"));
+            }
+            let bindings = field_bindings(&data.fields);
+            let pattern = fields_pattern(&data.fields, &bindings);
+            let statements = encode_fields(&data.fields, &bindings)?;
+            quote! {
+                let #name #pattern = self;
+                #statements
+                ::core::result::Result::Ok(())
+            }
+        }
+        Data::Enum(data) => {
+            let tags = variant_tags(data, container.bits)?;
+            let mut arms = Vec::new();
+            for (variant, tag) in data.variants.iter().zip(tags) {
+                let variant_name = &variant.ident;
+                let bindings = field_bindings(&variant.fields);
+                let pattern = fields_pattern(&variant.fields, &bindings);
+                let statements = encode_fields(&variant.fields, &bindings)?;
+                let tag = match container.bits {
+                    Some(bits) => quote!(#trait_path::write_bits(#writer, #tag, #bits)?;),
+                    None => {
+                        let tag = tag as u32;
+                        quote!(#trait_path::write_u32(#writer, #tag)?;)
+                    }
+                };
+                arms.push(quote! {
+                    #name::#variant_name #pattern => {
+                        #tag
+                        #statements
+                    }
+                });
+            }
+            if arms.is_empty() {
+                // 空枚举没有值，match self {} 无法通过穷尽性检查
+                quote!(match *self {})
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                    ::core::result::Result::Ok(())
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(name, "StreamEncode cannot be derived for unions"))
+        }
+    };
+    let body = match &container.endianness {
+        Some(_) => with_endianness(
+            &container.endianness,
+            &writer,
+            &trait_path,
+            quote!((|| -> ::memory_stream::error::Result<()> { #body })()),
+        ),
+        None => body,
+    };
+
+    let generics = add_bounds(&input.generics, quote!(::memory_stream::codec::Encode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::memory_stream::codec::Encode for #name #ty_generics #where_clause {
+            fn encode<__W: ::memory_stream::stream::StreamWriter + ?Sized>(
+                &self,
+                #writer: &mut __W,
+            ) -> ::memory_stream::error::Result<()> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_decode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let container = parse_attrs(&input.attrs)?;
+    if container.skip {
+        return Err(syn::Error::new_spanned(name, "#[skip] is only allowed on fields"));
+    }
+    let trait_path = quote!(::memory_stream::stream::StreamReader);
+    let reader = reader_ident();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            if container.bits.is_some() {
+                return Err(syn::Error::new_spanned(name, "#[bits] on a struct is not supported"));
+            }
+            let bindings = field_bindings(&data.fields);
+            let pattern = fields_pattern(&data.fields, &bindings);
+            let statements = decode_fields(&data.fields, &bindings)?;
+            quote! {
+                #statements
+                ::core::result::Result::Ok(#name #pattern)
+            }
+        }
+        Data::Enum(data) => {
+            let tags = variant_tags(data, container.bits)?;
+            let mut arms = Vec::new();
+            for (variant, tag) in data.variants.iter().zip(tags) {
+                let variant_name = &variant.ident;
+                let bindings = field_bindings(&variant.fields);
+                let pattern = fields_pattern(&variant.fields, &bindings);
+                let statements = decode_fields(&variant.fields, &bindings)?;
+                arms.push(quote! {
+                    #tag => {
+                        #statements
+                        ::core::result::Result::Ok(#name::#variant_name #pattern)
+                    }
+                });
+            }
+            let tag = match container.bits {
+                Some(bits) => quote!(#trait_path::read_bits(#reader, #bits)?),
+                None => quote!(#trait_path::read_u32(#reader)? as u64),
+            };
+            let type_name = name.to_string();
+            quote! {
+                let __tag: u64 = #tag;
+                match __tag {
+                    #(#arms)*
+                    _ => ::core::result::Result::Err(
+                        ::memory_stream::error::StreamError::UnknownVariant {
+                            type_name: #type_name,
+                            tag: __tag,
+                        },
+                    ),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(name, "StreamDecode cannot be derived for unions"))
+        }
+    };
+    let body = match &container.endianness {
+        Some(_) => with_endianness(
+            &container.endianness,
+            &reader,
+            &trait_path,
+            quote!((|| -> ::memory_stream::error::Result<Self> { #body })()),
+        ),
+        None => body,
+    };
+    // 枚举只计算标签占用的位数
+    let min_bits = match &input.data {
+        Data::Struct(data) => fields_min_bits(&data.fields)?,
+        _ => {
+            let bits = container.bits.unwrap_or(32) as usize;
+            quote!(#bits)
+        }
+    };
+
+    let generics = add_bounds(&input.generics, quote!(::memory_stream::codec::Decode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::memory_stream::codec::Decode for #name #ty_generics #where_clause {
+            const MIN_BITS: usize = #min_bits;
+
+            fn decode<__R: ::memory_stream::stream::StreamReader + ?Sized>(
+                #reader: &mut __R,
+            ) -> ::memory_stream::error::Result<Self> {
+                #body
+            }
+        }
+    })
+}
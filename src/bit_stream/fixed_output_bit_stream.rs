@@ -0,0 +1,320 @@
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use crate::bit_stream::output_bit_stream::{BitSlot, patch_byte, put_byte};
+use crate::byte_stream::Endianness;
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::{Result, StreamError};
+use crate::stream;
+use crate::stream::StreamWriter;
+
+macro_rules! write_endianness {
+    ( $self: ident, $value: expr) => {{
+        if $self.endianness == Endianness::BigEndian {
+            $self.write_raw(&$value.to_be_bytes())
+        } else {
+            $self.write_raw(&$value.to_le_bytes())
+        }
+    }};
+}
+
+macro_rules! reserve_patch {
+    ( $( $t:ty => $reserve:ident, $patch:ident; )* ) => {
+        $(
+            pub fn $reserve(&mut self) -> Result<BitSlot<$t>> {
+                self.reserve_slot(size_of::<$t>() * 8)
+            }
+
+            pub fn $patch(&mut self, slot: BitSlot<$t>, value: $t) {
+                let bytes = if self.endianness == Endianness::BigEndian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                for (i, byte) in bytes.iter().enumerate() {
+                    patch_byte(self.buf, slot.bit_offset + i * 8, *byte, 8);
+                }
+            }
+        )*
+    };
+}
+
+// 写入调用方提供的定长缓冲区，空间不足时返回错误而不是扩容
+pub struct FixedOutputBitStream<'a> {
+    buf: &'a mut [u8],
+    bit_head: usize,
+    pub endianness: Endianness,
+    length_prefix: LengthPrefix,
+}
+
+impl<'a> FixedOutputBitStream<'a> {
+    pub fn new(buf: &'a mut [u8], endianness: Endianness) -> FixedOutputBitStream<'a> {
+        FixedOutputBitStream::with_config(buf, &StreamConfig::new().endianness(endianness))
+    }
+
+    // 容量由 buf 决定，配置了 max_size 时只使用 buf 的前 max_size 个字节
+    pub fn with_config(buf: &'a mut [u8], config: &StreamConfig) -> FixedOutputBitStream<'a> {
+        let len = config.get_max_size().map_or(buf.len(), |max_size| max_size.min(buf.len()));
+        FixedOutputBitStream {
+            buf: &mut buf[..len],
+            bit_head: 0,
+            endianness: config.get_endianness(),
+            length_prefix: config.get_length_prefix(),
+        }
+    }
+
+    #[inline]
+    fn bit_offset(&self) -> usize {
+        self.bit_head & 0x7
+    }
+
+    pub fn capacity_bits(&self) -> usize {
+        self.buf.len() * 8
+    }
+
+    pub fn remaining_bits(&self) -> usize {
+        self.buf.len() * 8 - self.bit_head
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bit_head
+    }
+
+    fn check_bits(&self, bit_count: usize) -> Result<()> {
+        if bit_count > self.remaining_bits() {
+            return Err(StreamError::NotEnoughBitSpace {
+                requested: bit_count,
+                bit_offset: self.bit_head,
+                remaining: self.remaining_bits(),
+            });
+        }
+        Ok(())
+    }
+
+    // 调用方已经检查过空间
+    fn put_bits(&mut self, value: u64, bit_count: usize) {
+        let mut value = value;
+        let mut bit_count = bit_count;
+        while bit_count > 0 {
+            let count = bit_count.min(8);
+            put_byte(self.buf, self.bit_head, value as u8, count);
+            self.bit_head += count;
+            value = value.checked_shr(count as u32).unwrap_or(0);
+            bit_count -= count;
+        }
+    }
+
+    // 空间不足时不写入任何数据。按字节对齐时一次复制写入，否则逐字节移位写入
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.check_bits(data.len() * 8)?;
+        if self.bit_offset() == 0 {
+            let start = self.bit_head >> 3;
+            self.buf[start..start + data.len()].copy_from_slice(data);
+            self.bit_head += data.len() * 8;
+            return Ok(());
+        }
+        for byte in data {
+            self.put_bits(*byte as u64, 8);
+        }
+        Ok(())
+    }
+
+    pub fn write_bits(&mut self, value: u64, bit_count: u32) -> Result<()> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        self.check_bits(bit_count as usize)?;
+        self.put_bits(value, bit_count as usize);
+        Ok(())
+    }
+
+    pub fn write_signed_bits(&mut self, value: i64, bit_count: u32) -> Result<()> {
+        self.write_bits(value as u64, bit_count)
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<()> {
+        self.write_bits(value as u64, 1)
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<()> {
+        self.write_bits(value as u64, 8)
+    }
+    pub fn write_i8(&mut self, value: i8) -> Result<()> {
+        self.write_u8(value as u8)
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> Result<()> {
+        write_endianness!(self, value)
+    }
+    pub fn write_i16(&mut self, value: i16) -> Result<()> {
+        self.write_u16(value as u16)
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> Result<()> {
+        write_endianness!(self, value)
+    }
+    pub fn write_i32(&mut self, value: i32) -> Result<()> {
+        self.write_u32(value as u32)
+    }
+
+    pub fn write_u64(&mut self, value: u64) -> Result<()> {
+        write_endianness!(self, value)
+    }
+    pub fn write_i64(&mut self, value: i64) -> Result<()> {
+        self.write_u64(value as u64)
+    }
+
+    pub fn write_u128(&mut self, value: u128) -> Result<()> {
+        write_endianness!(self, value)
+    }
+    pub fn write_i128(&mut self, value: i128) -> Result<()> {
+        self.write_u128(value as u128)
+    }
+
+    pub fn write_f32(&mut self, value: f32) -> Result<()> {
+        self.write_u32(value.to_bits())
+    }
+    pub fn write_f64(&mut self, value: f64) -> Result<()> {
+        self.write_u64(value.to_bits())
+    }
+
+    pub fn write_var_u32(&mut self, value: u32) -> Result<()> {
+        self.write_var_u64(value as u64)
+    }
+    pub fn write_var_u64(&mut self, value: u64) -> Result<()> {
+        let mut bytes = [0u8; 10];
+        let len = stream::encode_var(value, &mut bytes);
+        self.write_raw(&bytes[..len])
+    }
+
+    pub fn write_var_i32(&mut self, value: i32) -> Result<()> {
+        self.write_var_u32(((value << 1) ^ (value >> 31)) as u32)
+    }
+    pub fn write_var_i64(&mut self, value: i64) -> Result<()> {
+        self.write_var_u64(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    // 按配置的长度前缀写入长度
+    pub fn write_len(&mut self, len: usize) -> Result<()> {
+        StreamWriter::write_len(self, len)
+    }
+
+    // 长度前缀和内容一起检查空间，不会只写入前缀
+    pub fn write_string(&mut self, data: &str) -> Result<()> {
+        stream::check_len(self.length_prefix, data.len())?;
+        self.check_bits((stream::len_prefix_size(self.length_prefix, data.len()) + data.len()) * 8)?;
+        self.write_len(data.len())?;
+        self.write_raw(data.as_bytes())
+    }
+
+    pub fn write_string_var(&mut self, data: &str) -> Result<()> {
+        let mut prefix = [0u8; 10];
+        let len = stream::encode_var(data.len() as u64, &mut prefix);
+        self.check_bits((len + data.len()) * 8)?;
+        self.write_raw(&prefix[..len])?;
+        self.write_raw(data.as_bytes())
+    }
+
+    // 用 0 填充到下一个字节边界
+    pub fn align_to_byte(&mut self) -> Result<()> {
+        let bit_offset = self.bit_offset();
+        if bit_offset != 0 {
+            self.write_bits(0, 8 - bit_offset as u32)?;
+        }
+        Ok(())
+    }
+
+    // 用 0 填充到总长度为 bit_len 位，空间不足时不写入任何数据
+    pub fn pad_to_bits(&mut self, bit_len: usize) -> Result<()> {
+        assert!(
+            bit_len >= self.bit_head,
+            "cannot pad to {} bits, {} bits already written",
+            bit_len,
+            self.bit_head
+        );
+        self.check_bits(bit_len - self.bit_head)?;
+        self.put_bits(0, bit_len - self.bit_head);
+        Ok(())
+    }
+
+    // 写入 bit_count 个 0 作为占位
+    fn reserve_slot<T>(&mut self, bit_count: usize) -> Result<BitSlot<T>> {
+        let bit_offset = self.bit_head;
+        self.pad_to_bits(bit_offset + bit_count)?;
+        Ok(BitSlot {
+            bit_offset,
+            bit_count,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn reserve_bits(&mut self, bit_count: u32) -> Result<BitSlot<u64>> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        self.reserve_slot(bit_count as usize)
+    }
+
+    // 和 write_bits 一样写入 value 的低位
+    pub fn patch_bits(&mut self, slot: BitSlot<u64>, value: u64) {
+        let mut value = value;
+        let mut bit_pos = slot.bit_offset;
+        let mut bit_count = slot.bit_count;
+        while bit_count > 0 {
+            let count = bit_count.min(8);
+            patch_byte(self.buf, bit_pos, value as u8, count);
+            value = value.checked_shr(count as u32).unwrap_or(0);
+            bit_pos += count;
+            bit_count -= count;
+        }
+    }
+
+    reserve_patch! {
+        u8 => reserve_u8, patch_u8;
+        u16 => reserve_u16, patch_u16;
+        u32 => reserve_u32, patch_u32;
+        u64 => reserve_u64, patch_u64;
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[0..self.bit_head.div_ceil(8)]
+    }
+
+    // 返回原缓冲区中已写入的部分和准确的位数，最后一个字节中的填充位保证为 0
+    pub fn finish(self) -> (&'a mut [u8], usize) {
+        let bit_len = self.bit_head;
+        let bit_offset = self.bit_offset();
+        let bytes = &mut self.buf[0..bit_len.div_ceil(8)];
+        if bit_offset != 0 {
+            if let Some(last) = bytes.last_mut() {
+                *last &= !(0xffu8 << bit_offset);
+            }
+        }
+        (bytes, bit_len)
+    }
+}
+
+impl<'a> StreamWriter for FixedOutputBitStream<'a> {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.length_prefix }
+
+    fn check_space(&self, num_bytes: usize) -> Result<()> { self.check_bits(num_bytes.saturating_mul(8)) }
+
+    fn write_bool(&mut self, value: bool) -> Result<()> { FixedOutputBitStream::write_bool(self, value) }
+
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> { FixedOutputBitStream::write_raw(self, data) }
+
+    fn write_u8(&mut self, value: u8) -> Result<()> { FixedOutputBitStream::write_u8(self, value) }
+    fn write_u16(&mut self, value: u16) -> Result<()> { FixedOutputBitStream::write_u16(self, value) }
+    fn write_u32(&mut self, value: u32) -> Result<()> { FixedOutputBitStream::write_u32(self, value) }
+    fn write_u64(&mut self, value: u64) -> Result<()> { FixedOutputBitStream::write_u64(self, value) }
+    fn write_u128(&mut self, value: u128) -> Result<()> { FixedOutputBitStream::write_u128(self, value) }
+
+    fn write_bits(&mut self, value: u64, bit_count: u32) -> Result<()> {
+        FixedOutputBitStream::write_bits(self, value, bit_count)
+    }
+
+    fn write_var_u32(&mut self, value: u32) -> Result<()> { FixedOutputBitStream::write_var_u32(self, value) }
+    fn write_var_u64(&mut self, value: u64) -> Result<()> { FixedOutputBitStream::write_var_u64(self, value) }
+
+    fn write_string(&mut self, data: &str) -> Result<()> { FixedOutputBitStream::write_string(self, data) }
+    fn write_string_var(&mut self, data: &str) -> Result<()> { FixedOutputBitStream::write_string_var(self, data) }
+}
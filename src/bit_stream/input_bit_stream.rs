@@ -1,33 +1,40 @@
-use std::mem::{size_of, transmute};
+use std::io::SeekFrom;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::byte_stream::Endianness;
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::{Result, StreamError};
+use crate::stream;
+use crate::stream::{StreamReader, StringPadding};
 
 macro_rules! read_be {
     ( $self: ident, $t:ty ) => {{
-        const SIZE: usize = size_of::<$t>();
-        let bytes = $self.read_bytes(SIZE);
-        let ptr = bytes.as_ptr() as *const [u8; SIZE];
+        let bytes = $self.read::<{ size_of::<$t>() }>()?;
         if $self.endianness == Endianness::BigEndian {
-            <$t>::from_be_bytes(unsafe { ptr.read() })
+            Ok(<$t>::from_be_bytes(bytes))
         } else {
-            <$t>::from_le_bytes(unsafe { ptr.read() })
+            Ok(<$t>::from_le_bytes(bytes))
         }
     }};
 }
 
 pub struct InputBitStream<'a> {
-    pub buf: &'a [u8],
-    pub bit_head: usize,
+    buf: &'a [u8],
+    bit_head: usize,
+    // 可读取的范围 [bit_start, bit_end)，bit_head 和这两个值都是相对 buf 开头的位偏移
+    bit_start: usize,
+    bit_end: usize,
     pub endianness: Endianness,
+    config: StreamConfig,
+    // 解码字符串和集合已经分配的字节数，和 take 出来的子流共享。只在配置了 max_alloc 时创建
+    allocated: Option<Arc<AtomicUsize>>,
 }
 
 impl<'a> Default for InputBitStream<'a> {
     fn default() -> Self {
-        Self {
-            buf: &[],
-            bit_head: 0,
-            endianness: Endianness::BigEndian,
-        }
+        InputBitStream::new(&[])
     }
 }
 
@@ -42,14 +49,132 @@ impl<'a> InputBitStream<'a> {
     }
 
     pub fn new(buf: &'a [u8]) -> InputBitStream<'a> {
+        InputBitStream::with_config(buf, &StreamConfig::new())
+    }
+
+    pub fn with_config(buf: &'a [u8], config: &StreamConfig) -> InputBitStream<'a> {
         InputBitStream {
             buf,
             bit_head: 0,
-            endianness: Endianness::BigEndian,
+            bit_start: 0,
+            bit_end: buf.len() * 8,
+            endianness: config.get_endianness(),
+            config: *config,
+            allocated: config.get_max_alloc().map(|_| Arc::new(AtomicUsize::new(0))),
+        }
+    }
+
+    // 只读取前 bit_len 位，通常和 OutputBitStream::finish 返回的位数配合使用
+    pub fn with_bit_len(buf: &'a [u8], bit_len: usize) -> Result<InputBitStream<'a>> {
+        if bit_len > buf.len() * 8 {
+            return Err(StreamError::NotEnoughBits {
+                requested: bit_len,
+                bit_offset: 0,
+                remaining: buf.len() * 8,
+            });
+        }
+        Ok(InputBitStream {
+            bit_end: bit_len,
+            ..InputBitStream::new(buf)
+        })
+    }
+
+    // 位置和长度都相对于可读取范围的开头，take 返回的流从 0 开始
+    pub fn bit_len(&self) -> usize {
+        self.bit_end - self.bit_start
+    }
+
+    pub fn bit_position(&self) -> usize {
+        self.bit_head - self.bit_start
+    }
+
+    pub fn remaining_bits(&self) -> usize {
+        self.bit_end - self.bit_head
+    }
+
+    // 以位为单位移动读取位置，超出范围时返回错误且不移动读取位置
+    pub fn seek_bits(&mut self, pos: SeekFrom) -> Result<usize> {
+        let len = self.bit_len();
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => len as i128 + offset as i128,
+            SeekFrom::Current(offset) => self.bit_position() as i128 + offset as i128,
+        };
+        if position < 0 || position > len as i128 {
+            return Err(StreamError::SeekOutOfBounds {
+                position: position.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+                len,
+            });
+        }
+        self.bit_head = self.bit_start + position as usize;
+        Ok(position as usize)
+    }
+
+    // 返回只能读取接下来 bit_count 位的流，当前流移动到这些数据之后，不复制数据
+    pub fn take(&mut self, bit_count: usize) -> Result<InputBitStream<'a>> {
+        self.check_bits(bit_count)?;
+        let end = self.bit_head + bit_count;
+        let stream = InputBitStream {
+            buf: &self.buf[self.byte_offset()..end.div_ceil(8)],
+            bit_head: self.bit_offset(),
+            bit_start: self.bit_offset(),
+            bit_end: self.bit_offset() + bit_count,
+            endianness: self.endianness,
+            config: self.config,
+            allocated: self.allocated.clone(),
+        };
+        self.bit_head = end;
+        Ok(stream)
+    }
+
+    pub fn skip_bits(&mut self, bit_count: usize) -> Result<()> {
+        self.check_bits(bit_count)?;
+        self.bit_head += bit_count;
+        Ok(())
+    }
+
+    // 读取 bit_count 位但不移动读取位置
+    pub fn peek_bits(&mut self, bit_count: u32) -> Result<u64> {
+        let bit_head = self.bit_head;
+        let result = self.read_bits(bit_count);
+        self.bit_head = bit_head;
+        result
+    }
+
+    // 跳过当前字节剩余的位，已经对齐时不移动。
+    // 配置了 strict_padding 时填充位不为 0 返回错误且不移动读取位置
+    pub fn align_to_byte(&mut self) -> Result<()> {
+        let bit_offset = self.bit_offset();
+        if bit_offset != 0 {
+            let padding = self.peek_bits(8 - bit_offset as u32)?;
+            if padding != 0 && self.config.is_strict_padding() {
+                return Err(StreamError::NonZeroPadding { bit_offset: self.bit_position() });
+            }
+            self.skip_bits(8 - bit_offset)?;
         }
+        Ok(())
     }
+
+    // 检查剩余位数是否足够读取 bit_count 位
+    fn check_bits(&self, bit_count: usize) -> Result<()> {
+        let remaining = self.remaining_bits();
+        if bit_count > remaining {
+            return Err(StreamError::NotEnoughBits {
+                requested: bit_count,
+                bit_offset: self.bit_position(),
+                remaining,
+            });
+        }
+        Ok(())
+    }
+
     // 读取最多一个字节，允许读取 <= 8 数据。如果当前字节剩余位数不足，和下一个字节组合成一个 u8
-    fn read_byte(&mut self, bit_count: usize) -> u8 {
+    fn read_byte(&mut self, bit_count: usize) -> Result<u8> {
+        self.check_bits(bit_count)?;
+        if bit_count == 0 {
+            return Ok(0);
+        }
+
         // 计算字节偏移和位偏移
         let byte_offset = self.byte_offset();
         let bit_offset = self.bit_offset();
@@ -63,63 +188,299 @@ impl<'a> InputBitStream<'a> {
         }
         out_data &= !(0xffu16 << bit_count) as u8;
         self.bit_head += bit_count;
-        out_data
+        Ok(out_data)
     }
 
-    fn read_bytes(&mut self, byte_count: usize) -> Vec<u8> {
+    // 先检查整体长度，避免读取到一半失败导致读取位置停在中间
+    fn read_bytes(&mut self, byte_count: usize) -> Result<Vec<u8>> {
+        self.check_bits(byte_count.saturating_mul(8))?;
         let mut bytes = vec![0u8; byte_count];
-        for i in 0..byte_count {
-            bytes[i] = self.read_byte(8);
+        self.read_raw(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // 填满 out，剩余数据不足时返回错误且不移动读取位置。
+    // 按字节对齐时一次复制读取，否则逐字节移位读取
+    pub fn read_raw(&mut self, out: &mut [u8]) -> Result<()> {
+        self.check_bits(out.len().saturating_mul(8))?;
+        if self.bit_offset() == 0 {
+            let start = self.byte_offset();
+            out.copy_from_slice(&self.buf[start..start + out.len()]);
+            self.bit_head += out.len() * 8;
+            return Ok(());
         }
-        bytes
+        for byte in out.iter_mut() {
+            *byte = self.read_byte(8)?;
+        }
+        Ok(())
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.read_raw(&mut bytes)?;
+        Ok(bytes)
     }
 
-    fn read<T>(&mut self) -> T {
-        unsafe {
-            let bytes = self.read_bytes(size_of::<T>());
-            std::ptr::read(bytes.as_ptr() as *const _)
+    // 返回输入数据中接下来 len 个字节的切片，不复制数据。
+    // 只有读取位置按字节对齐时才能返回切片，否则返回错误
+    pub fn read_byte_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bit_offset() != 0 {
+            return Err(StreamError::NotByteAligned { bit_offset: self.bit_position() });
         }
+        self.check_bits(len.saturating_mul(8))?;
+        let start = self.byte_offset();
+        self.bit_head += len * 8;
+        Ok(&self.buf[start..start + len])
     }
 
-    pub fn read_bool(&mut self) -> bool {
-        self.read_byte(1) == 1
+    // 读取 bit_count 位无符号整数，bit_count 最大为 64
+    pub fn read_bits(&mut self, bit_count: u32) -> Result<u64> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        self.check_bits(bit_count as usize)?;
+        let mut value = 0u64;
+        let mut shift = 0;
+        while shift < bit_count {
+            let count = (bit_count - shift).min(8);
+            value |= (self.read_byte(count as usize)? as u64) << shift;
+            shift += count;
+        }
+        Ok(value)
+    }
+
+    // 读取 bit_count 位补码整数并做符号扩展
+    pub fn read_signed_bits(&mut self, bit_count: u32) -> Result<i64> {
+        let value = self.read_bits(bit_count)?;
+        if bit_count == 0 {
+            return Ok(0);
+        }
+        let shift = 64 - bit_count;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_byte(1)? == 1)
     }
 
-    pub fn read_u8(&mut self) -> u8 {
-        self.read()
+    pub fn read_u8(&mut self) -> Result<u8> {
+        self.read_byte(8)
     }
-    pub fn read_i8(&mut self) -> i8 {
-        self.read()
+    pub fn read_i8(&mut self) -> Result<i8> {
+        Ok(self.read_byte(8)? as i8)
     }
 
-    pub fn read_u16(&mut self) -> u16 {
+    pub fn read_u16(&mut self) -> Result<u16> {
         read_be!(self, u16)
     }
-    pub fn read_i16(&mut self) -> i16 {
+    pub fn read_i16(&mut self) -> Result<i16> {
         read_be!(self, i16)
     }
 
-    pub fn read_u32(&mut self) -> u32 {
+    pub fn read_u32(&mut self) -> Result<u32> {
         read_be!(self, u32)
     }
-    pub fn read_i32(&mut self) -> i32 {
+    pub fn read_i32(&mut self) -> Result<i32> {
         read_be!(self, i32)
     }
 
-    pub fn read_u64(&mut self) -> u64 {
+    pub fn read_u64(&mut self) -> Result<u64> {
         read_be!(self, u64)
     }
-    pub fn read_i64(&mut self) -> i64 {
+    pub fn read_i64(&mut self) -> Result<i64> {
         read_be!(self, i64)
     }
 
-    pub fn read_f32(&mut self) -> f32 {
-        unsafe { transmute(self.read_u32()) }
+    pub fn read_u128(&mut self) -> Result<u128> {
+        read_be!(self, u128)
+    }
+    pub fn read_i128(&mut self) -> Result<i128> {
+        read_be!(self, i128)
+    }
+
+    // 读取失败时回到变长整数开始的位置
+    fn read_var(&mut self, bits: u32) -> Result<u64> {
+        let start = self.bit_head;
+        let result = stream::read_var(bits, self.bit_position(), || self.read_byte(8));
+        if result.is_err() {
+            self.bit_head = start;
+        }
+        result
     }
 
-    pub fn read_string(&mut self) -> String {
-        let len = self.read_u32() as usize;
-        let bytes = self.read_bytes(len);
-        unsafe { String::from_utf8_unchecked(bytes) }
+    pub fn read_var_u32(&mut self) -> Result<u32> {
+        Ok(self.read_var(32)? as u32)
+    }
+    pub fn read_var_u64(&mut self) -> Result<u64> {
+        self.read_var(64)
+    }
+
+    pub fn read_var_i32(&mut self) -> Result<i32> {
+        let data = self.read_var_u32()?;
+        Ok((data >> 1) as i32 ^ -((data & 1) as i32))
+    }
+    pub fn read_var_i64(&mut self) -> Result<i64> {
+        let data = self.read_var_u64()?;
+        Ok((data >> 1) as i64 ^ -((data & 1) as i64))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    // 按配置的长度前缀读取长度
+    pub fn read_len(&mut self) -> Result<usize> {
+        StreamReader::read_len(self)
+    }
+
+    // 在分配内存之前检查长度：不超过配置的限制，也不超过剩余的数据
+    pub fn check_string_len(&mut self, len: usize) -> Result<()> {
+        self.check_bits(len.saturating_mul(8))?;
+        stream::charge_string(&self.config, self.allocated.as_deref(), len)
+    }
+
+    // 每个元素至少占 min_bits 位，分配内存前检查剩余数据
+    pub fn check_collection_len(&mut self, len: usize, elem_size: usize, min_bits: usize) -> Result<()> {
+        self.check_bits(len.saturating_mul(min_bits))?;
+        stream::charge_collection(&self.config, self.allocated.as_deref(), len, elem_size)
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.allocated.as_ref().map_or(0, |allocated| allocated.load(Ordering::Relaxed))
+    }
+
+    // 开始解码下一个消息前清零，重新计算 max_alloc
+    pub fn reset_allocated(&mut self) {
+        if let Some(allocated) = &self.allocated {
+            allocated.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // 失败时恢复读取位置，长度超出限制或数据不足时不会只消耗长度前缀
+    fn rewind_on_err<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let bit_head = self.bit_head;
+        let allocated = self.allocated();
+        let result = read(self);
+        if result.is_err() {
+            self.bit_head = bit_head;
+            // 已经计入 max_alloc 的字节一起退还，重试时不会重复计算
+            stream::refund_alloc(self.allocated.as_deref(), self.allocated().saturating_sub(allocated));
+        }
+        result
+    }
+
+    // 失败时不移动读取位置
+    pub fn read_string(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?;
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    pub fn read_string_var(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_var_u32()? as usize;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?;
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    // 和 read_string 相同，但返回输入数据中的切片，要求长度前缀之后的位置按字节对齐。
+    // 不分配内存，只检查 max_string_len。失败时不移动读取位置
+    pub fn read_str(&mut self) -> Result<&'a str> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            stream::check_string_limit(&input.config, len)?;
+            let bytes = input.read_byte_slice(len)?;
+            std::str::from_utf8(bytes).map_err(StreamError::InvalidUtf8)
+        })
+    }
+
+    // 先找到结尾的 0 字节再分配内存，没有找到或者内容不是合法的 UTF-8 时返回错误且不移动读取位置
+    pub fn read_string_nul(&mut self) -> Result<String> {
+        let bit_head = self.bit_head;
+        let mut len = 0;
+        let mut found = false;
+        while self.remaining_bits() >= 8 {
+            if self.read_byte(8)? == 0 {
+                found = true;
+                break;
+            }
+            len += 1;
+        }
+        self.bit_head = bit_head;
+        if !found {
+            return Err(StreamError::MissingNulTerminator { len });
+        }
+        self.rewind_on_err(|input| {
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?;
+            input.skip_bits(8)?;
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    // 非法的 UTF-8 序列替换为 U+FFFD，失败时不移动读取位置
+    pub fn read_string_lossy(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?;
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        })
+    }
+}
+
+impl<'a> StreamReader for InputBitStream<'a> {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.config.get_length_prefix() }
+
+    fn read_bool(&mut self) -> Result<bool> { InputBitStream::read_bool(self) }
+
+    fn read_raw(&mut self, out: &mut [u8]) -> Result<()> { InputBitStream::read_raw(self, out) }
+
+    fn read_u8(&mut self) -> Result<u8> { InputBitStream::read_u8(self) }
+    fn read_u16(&mut self) -> Result<u16> { InputBitStream::read_u16(self) }
+    fn read_u32(&mut self) -> Result<u32> { InputBitStream::read_u32(self) }
+    fn read_u64(&mut self) -> Result<u64> { InputBitStream::read_u64(self) }
+    fn read_u128(&mut self) -> Result<u128> { InputBitStream::read_u128(self) }
+
+    // 不完整或者非法的 UTF-8 序列不移动读取位置
+    fn read_char(&mut self) -> Result<char> { self.rewind_on_err(stream::read_utf8_char) }
+
+    fn read_bits(&mut self, bit_count: u32) -> Result<u64> { InputBitStream::read_bits(self, bit_count) }
+
+    fn read_var_u32(&mut self) -> Result<u32> { InputBitStream::read_var_u32(self) }
+    fn read_var_u64(&mut self) -> Result<u64> { InputBitStream::read_var_u64(self) }
+
+    fn check_string_len(&mut self, len: usize) -> Result<()> { InputBitStream::check_string_len(self, len) }
+    fn check_collection_len(&mut self, len: usize, elem_size: usize, min_bits: usize) -> Result<()> {
+        InputBitStream::check_collection_len(self, len, elem_size, min_bits)
+    }
+
+    fn read_string(&mut self) -> Result<String> { InputBitStream::read_string(self) }
+    fn read_string_lossy(&mut self) -> Result<String> { InputBitStream::read_string_lossy(self) }
+    fn read_string_var(&mut self) -> Result<String> { InputBitStream::read_string_var(self) }
+    fn read_string_nul(&mut self) -> Result<String> { InputBitStream::read_string_nul(self) }
+
+    // 这些读取没有对应的公开方法，同样在失败时恢复读取位置
+    fn read_string_u8(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_prefixed_string(input, LengthPrefix::U8))
+    }
+    fn read_string_u16(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_prefixed_string(input, LengthPrefix::U16))
+    }
+    fn read_string_fixed(&mut self, width: usize, padding: StringPadding) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_fixed_string(input, width, padding))
+    }
+    fn read_string_utf16(&mut self, endianness: Endianness) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_utf16_string(input, endianness))
     }
 }
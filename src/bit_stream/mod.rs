@@ -1,2 +1,3 @@
 pub mod output_bit_stream;
-pub mod input_bit_stream;
\ No newline at end of file
+pub mod input_bit_stream;
+pub mod fixed_output_bit_stream;
\ No newline at end of file
@@ -1,23 +1,100 @@
-use std::cmp::max;
-use std::mem::{size_of_val, transmute};
-use std::ptr::addr_of;
+use std::marker::PhantomData;
+use std::mem::size_of;
 
-use crate::byte_stream::Endianness;
+use crate::byte_stream::{Endianness, grow_buffer};
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::Result;
+use crate::stream;
+use crate::stream::StreamWriter;
 
 macro_rules! write_endianness {
     ( $self: ident, $value: expr) => {{
         if $self.endianness == Endianness::BigEndian {
-            $self.write(&$value.to_be())
+            $self.write_raw(&$value.to_be_bytes())
         } else {
-            $self.write(&$value.to_le())
+            $self.write_raw(&$value.to_le_bytes())
         }
     }};
 }
 
+// 在 bit_head 处追加小于等于 8 位的数据，调用方保证 buf 足够长
+pub(crate) fn put_byte(buf: &mut [u8], bit_head: usize, data: u8, bit_count: usize) {
+    // 丢弃 bit_count 之外的高位，避免污染后面的数据
+    let data = data & !(0xffu16 << bit_count) as u8;
+
+    // 计算字节偏移和位偏移
+    let byte_offset = bit_head >> 3;
+    let bit_offset = bit_head & 0x7;
+
+    // 写入数据和原有数据进行整合
+    let current_mask = !(0xFF << bit_offset);
+    buf[byte_offset] = (buf[byte_offset] & current_mask) | (data << bit_offset);
+
+    let bits_free_this_byte = 8 - bit_offset;
+
+    // 将当前字节无法存下的剩余数据写入到下一个字节
+    if bits_free_this_byte < bit_count {
+        buf[byte_offset + 1] = data >> bits_free_this_byte;
+    }
+}
+
+// 覆盖已写入位置 bit_pos 开始的 bit_count 位，保留前后其他位的数据
+pub(crate) fn patch_byte(buf: &mut [u8], bit_pos: usize, data: u8, bit_count: usize) {
+    let byte_offset = bit_pos >> 3;
+    let bit_offset = bit_pos & 0x7;
+    let mask = !(0xffffu16 << bit_count) << bit_offset;
+    let data = (data as u16) << bit_offset & mask;
+
+    buf[byte_offset] = (buf[byte_offset] & !mask as u8) | data as u8;
+    if bit_offset + bit_count > 8 {
+        let next = &mut buf[byte_offset + 1];
+        *next = (*next & !(mask >> 8) as u8) | (data >> 8) as u8;
+    }
+}
+
+// reserve_* 预留的位置，之后用对应的 patch_* 填入数据
+#[derive(Debug, Clone, Copy)]
+pub struct BitSlot<T> {
+    pub(crate) bit_offset: usize,
+    pub(crate) bit_count: usize,
+    pub(crate) _marker: PhantomData<T>,
+}
+
+impl<T> BitSlot<T> {
+    pub fn bit_offset(&self) -> usize {
+        self.bit_offset
+    }
+    pub fn bit_count(&self) -> usize {
+        self.bit_count
+    }
+}
+
+macro_rules! reserve_patch {
+    ( $( $t:ty => $reserve:ident, $patch:ident; )* ) => {
+        $(
+            pub fn $reserve(&mut self) -> BitSlot<$t> {
+                self.reserve_slot(size_of::<$t>() * 8)
+            }
+
+            pub fn $patch(&mut self, slot: BitSlot<$t>, value: $t) {
+                let bytes = if self.endianness == Endianness::BigEndian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                for (i, byte) in bytes.iter().enumerate() {
+                    patch_byte(&mut self.buf, slot.bit_offset + i * 8, *byte, 8);
+                }
+            }
+        )*
+    };
+}
+
 pub struct OutputBitStream {
     pub buf: Vec<u8>,
     pub bit_head: usize,
     pub endianness: Endianness,
+    pub length_prefix: LengthPrefix,
 }
 
 impl Default for OutputBitStream {
@@ -26,6 +103,7 @@ impl Default for OutputBitStream {
             buf: vec![0; 1024],
             bit_head: 0,
             endianness: Endianness::BigEndian,
+            length_prefix: LengthPrefix::U32,
         }
     }
 }
@@ -40,6 +118,49 @@ impl OutputBitStream {
         self.bit_head & 0x7
     }
 
+    pub fn with_config(config: &StreamConfig) -> OutputBitStream {
+        OutputBitStream {
+            buf: Vec::with_capacity(config.get_capacity()),
+            bit_head: 0,
+            endianness: config.get_endianness(),
+            length_prefix: config.get_length_prefix(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize, endianness: Endianness) -> OutputBitStream {
+        OutputBitStream::with_config(&StreamConfig::new().capacity(capacity).endianness(endianness))
+    }
+
+    // 接着 vec 中已有的字节继续写入，需要复用空间时先调用 clear
+    pub fn from_vec(buf: Vec<u8>, endianness: Endianness) -> OutputBitStream {
+        OutputBitStream {
+            bit_head: buf.len() * 8,
+            buf,
+            ..OutputBitStream::with_capacity(0, endianness)
+        }
+    }
+
+    // 以字节为单位
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    // 保证之后再写入 additional 字节不会重新分配内存
+    pub fn reserve(&mut self, additional: usize) {
+        let len = (self.bit_head + additional * 8).div_ceil(8);
+        self.buf.reserve(len.saturating_sub(self.buf.len()));
+    }
+
+    // 回到开头重新写入，保留已分配的内存
+    pub fn clear(&mut self) {
+        self.bit_head = 0;
+    }
+
+    // 和 finish 相同，但不返回位数
+    pub fn into_vec(self) -> Vec<u8> {
+        self.finish().0
+    }
+
     pub fn buffer(&self) -> &[u8] {
         let mut head = self.byte_offset();
         if self.bit_offset() != 0 {
@@ -48,52 +169,133 @@ impl OutputBitStream {
         &self.buf[0..head]
     }
 
+    // 已写入的位数，buffer() 最后一个字节中超出这个长度的位是填充的 0
+    pub fn bit_len(&self) -> usize {
+        self.bit_head
+    }
+
+    // 用 0 填充到下一个字节边界，已经对齐时不写入
+    pub fn align_to_byte(&mut self) {
+        let bit_offset = self.bit_offset();
+        if bit_offset != 0 {
+            self.write_byte(0, 8 - bit_offset);
+        }
+    }
+
+    // 用 0 填充到总长度为 bit_len 位
+    pub fn pad_to_bits(&mut self, bit_len: usize) {
+        assert!(
+            bit_len >= self.bit_head,
+            "cannot pad to {} bits, {} bits already written",
+            bit_len,
+            self.bit_head
+        );
+        let mut bit_count = bit_len - self.bit_head;
+        while bit_count > 0 {
+            let count = bit_count.min(8);
+            self.write_byte(0, count);
+            bit_count -= count;
+        }
+    }
+
+    // 返回写入的字节和准确的位数，最后一个字节中的填充位保证为 0
+    pub fn finish(mut self) -> (Vec<u8>, usize) {
+        let bit_len = self.bit_head;
+        let bit_offset = self.bit_offset();
+        self.buf.truncate(bit_len.div_ceil(8));
+        if bit_offset != 0 {
+            if let Some(last) = self.buf.last_mut() {
+                *last &= !(0xffu8 << bit_offset);
+            }
+        }
+        (self.buf, bit_len)
+    }
+
     // 写入小于等于 8 位的数据
     fn write_byte(&mut self, data: u8, bit_count: usize) {
         assert!(bit_count <= 8);
-
-        let next_byte_head = (self.bit_head + bit_count) >> 3;
+        if bit_count == 0 {
+            return;
+        }
+        // 向上取整，保证写入的最后一个字节在缓冲区内
+        let next_byte_head = (self.bit_head + bit_count + 7) >> 3;
         if next_byte_head > self.buf.len() {
-            self.buf.resize(max(self.buf.len(), next_byte_head) * 2, 0);
+            grow_buffer(&mut self.buf, next_byte_head);
         }
 
-        // 计算字节偏移和位偏移
-        let byte_offset = self.byte_offset();
-        let bit_offset = self.bit_offset();
+        put_byte(&mut self.buf, self.bit_head, data, bit_count);
+        self.bit_head += bit_count;
+    }
 
-        // 写入数据和原有数据进行整合
-        let current_mask = !(0xFF << bit_offset);
-        self.buf[byte_offset] = (self.buf[byte_offset] & current_mask) | (data << bit_offset);
+    // 写入 bit_count 个 0 作为占位
+    fn reserve_slot<T>(&mut self, bit_count: usize) -> BitSlot<T> {
+        let bit_offset = self.bit_head;
+        self.pad_to_bits(bit_offset + bit_count);
+        BitSlot {
+            bit_offset,
+            bit_count,
+            _marker: PhantomData,
+        }
+    }
 
-        let bits_free_this_byte = 8 - bit_offset;
+    pub fn reserve_bits(&mut self, bit_count: u32) -> BitSlot<u64> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        self.reserve_slot(bit_count as usize)
+    }
 
-        // 将当前字节无法存下的剩余数据写入到下一个字节
-        if bits_free_this_byte < bit_count {
-            self.buf[byte_offset + 1] = data >> bits_free_this_byte;
+    // 和 write_bits 一样写入 value 的低位
+    pub fn patch_bits(&mut self, slot: BitSlot<u64>, value: u64) {
+        let mut value = value;
+        let mut bit_pos = slot.bit_offset;
+        let mut bit_count = slot.bit_count;
+        while bit_count > 0 {
+            let count = bit_count.min(8);
+            patch_byte(&mut self.buf, bit_pos, value as u8, count);
+            value = value.checked_shr(count as u32).unwrap_or(0);
+            bit_pos += count;
+            bit_count -= count;
         }
+    }
 
-        self.bit_head = self.bit_head + bit_count;
+    reserve_patch! {
+        u8 => reserve_u8, patch_u8;
+        u16 => reserve_u16, patch_u16;
+        u32 => reserve_u32, patch_u32;
+        u64 => reserve_u64, patch_u64;
     }
 
-    fn write_bytes(&mut self, data: *const u8, bit_count: usize) {
-        let mut bit_count = bit_count;
-        let mut offset = 0;
-        while bit_count > 8 {
-            unsafe {
-                self.write_byte(*data.offset(offset), 8);
+    // 按字节对齐时一次复制写入，否则逐字节移位写入
+    pub fn write_raw(&mut self, data: &[u8]) {
+        if self.bit_offset() != 0 {
+            for byte in data {
+                self.write_byte(*byte, 8);
             }
-            offset += 1;
-            bit_count -= 8;
+            return;
         }
-        if bit_count > 0 {
-            unsafe {
-                self.write_byte(*data.offset(offset), bit_count);
-            }
+        let start = self.byte_offset();
+        if start + data.len() > self.buf.len() {
+            grow_buffer(&mut self.buf, start + data.len());
         }
+        self.buf[start..start + data.len()].copy_from_slice(data);
+        self.bit_head += data.len() * 8;
     }
 
-    fn write<T>(&mut self, obj: &T) {
-        self.write_bytes(addr_of!(*obj) as *const u8, size_of_val(obj) * 8);
+    // 写入 value 的低 bit_count 位，bit_count 最大为 64
+    pub fn write_bits(&mut self, value: u64, bit_count: u32) {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        let mut value = value;
+        let mut bit_count = bit_count as usize;
+        while bit_count > 0 {
+            let count = bit_count.min(8);
+            self.write_byte(value as u8, count);
+            value = value.checked_shr(count as u32).unwrap_or(0);
+            bit_count -= count;
+        }
+    }
+
+    // 以补码形式写入 value 的低 bit_count 位
+    pub fn write_signed_bits(&mut self, value: i64, bit_count: u32) {
+        self.write_bits(value as u64, bit_count)
     }
 
     pub fn write_bool(&mut self, value: bool) {
@@ -101,10 +303,10 @@ impl OutputBitStream {
     }
 
     pub fn write_u8(&mut self, value: u8) {
-        self.write(&value)
+        self.write_byte(value, 8)
     }
     pub fn write_i8(&mut self, value: i8) {
-        self.write(&value)
+        self.write_byte(value as u8, 8)
     }
 
     pub fn write_u16(&mut self, value: u16) {
@@ -128,15 +330,121 @@ impl OutputBitStream {
         write_endianness!(self, value)
     }
 
+    pub fn write_u128(&mut self, value: u128) {
+        write_endianness!(self, value)
+    }
+    pub fn write_i128(&mut self, value: i128) {
+        write_endianness!(self, value)
+    }
+
     pub fn write_f32(&mut self, value: f32) {
-        self.write_u32(unsafe { transmute(value) })
+        self.write_u32(value.to_bits())
     }
 
-    pub fn write_string(&mut self, data: &String) {
-        self.write_u32(data.len() as u32);
-        let bytes = data.as_bytes();
-        for byte in bytes {
-            self.write_u8(*byte);
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits())
+    }
+
+    // LEB128，每个字节低 7 位存数据，最高位表示后面是否还有字节
+    pub fn write_var_u32(&mut self, value: u32) {
+        self.write_var_u64(value as u64)
+    }
+    pub fn write_var_u64(&mut self, value: u64) {
+        let mut value = value;
+        while value >= 0x80 {
+            self.write_u8(value as u8 | 0x80);
+            value >>= 7;
         }
+        self.write_u8(value as u8)
+    }
+
+    // zigzag 编码，把绝对值小的负数映射成小的正数
+    pub fn write_var_i32(&mut self, value: i32) {
+        self.write_var_u32(((value << 1) ^ (value >> 31)) as u32)
+    }
+    pub fn write_var_i64(&mut self, value: i64) {
+        self.write_var_u64(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    // 按 length_prefix 写入长度，超出前缀的范围时返回错误且不写入任何数据
+    pub fn write_len(&mut self, len: usize) -> Result<()> {
+        stream::check_len(self.length_prefix, len)?;
+        match self.length_prefix {
+            LengthPrefix::U8 => self.write_u8(len as u8),
+            LengthPrefix::U16 => self.write_u16(len as u16),
+            LengthPrefix::U32 => self.write_u32(len as u32),
+            LengthPrefix::VarInt => self.write_var_u32(len as u32),
+        }
+        Ok(())
+    }
+
+    pub fn write_string(&mut self, data: &str) -> Result<()> {
+        self.write_len(data.len())?;
+        self.write_raw(data.as_bytes());
+        Ok(())
+    }
+
+    // 使用变长整数作为长度前缀
+    pub fn write_string_var(&mut self, data: &str) {
+        self.write_var_u32(data.len() as u32);
+        self.write_raw(data.as_bytes());
+    }
+}
+
+impl StreamWriter for OutputBitStream {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.length_prefix }
+
+    fn write_bool(&mut self, value: bool) -> Result<()> {
+        OutputBitStream::write_bool(self, value);
+        Ok(())
+    }
+
+    fn write_u8(&mut self, value: u8) -> Result<()> {
+        OutputBitStream::write_u8(self, value);
+        Ok(())
+    }
+    fn write_u16(&mut self, value: u16) -> Result<()> {
+        OutputBitStream::write_u16(self, value);
+        Ok(())
+    }
+    fn write_u32(&mut self, value: u32) -> Result<()> {
+        OutputBitStream::write_u32(self, value);
+        Ok(())
+    }
+    fn write_u64(&mut self, value: u64) -> Result<()> {
+        OutputBitStream::write_u64(self, value);
+        Ok(())
+    }
+    fn write_u128(&mut self, value: u128) -> Result<()> {
+        OutputBitStream::write_u128(self, value);
+        Ok(())
+    }
+
+    fn write_bits(&mut self, value: u64, bit_count: u32) -> Result<()> {
+        OutputBitStream::write_bits(self, value, bit_count);
+        Ok(())
+    }
+
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        OutputBitStream::write_raw(self, data);
+        Ok(())
+    }
+
+    fn write_var_u32(&mut self, value: u32) -> Result<()> {
+        OutputBitStream::write_var_u32(self, value);
+        Ok(())
+    }
+    fn write_var_u64(&mut self, value: u64) -> Result<()> {
+        OutputBitStream::write_var_u64(self, value);
+        Ok(())
+    }
+
+    fn write_string(&mut self, data: &str) -> Result<()> { OutputBitStream::write_string(self, data) }
+    fn write_string_var(&mut self, data: &str) -> Result<()> {
+        OutputBitStream::write_string_var(self, data);
+        Ok(())
     }
 }
@@ -0,0 +1,193 @@
+use std::marker::PhantomData;
+
+use crate::byte_stream::Endianness;
+use crate::byte_stream::output_byte_stream::{Section, Slot};
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::{Result, StreamError};
+use crate::stream;
+use crate::stream::StreamWriter;
+
+macro_rules! write_endianness {
+    ( $self: ident, $value: expr) => {{
+        if $self.endianness == Endianness::BigEndian {
+            $self.write(&$value.to_be_bytes())
+        } else {
+            $self.write(&$value.to_le_bytes())
+        }
+    }};
+}
+
+macro_rules! reserve_patch {
+    ( $( $t:ty => $reserve:ident, $write:ident, $patch:ident; )* ) => {
+        $(
+            pub fn $reserve(&mut self) -> Result<Slot<$t>> {
+                let offset = self.head;
+                self.$write(0)?;
+                Ok(Slot { offset, _marker: PhantomData })
+            }
+
+            // 按流的字节序覆盖预留处的数据
+            pub fn $patch(&mut self, slot: Slot<$t>, data: $t) {
+                let bytes = if self.endianness == Endianness::BigEndian {
+                    data.to_be_bytes()
+                } else {
+                    data.to_le_bytes()
+                };
+                self.buf[slot.offset..slot.offset + bytes.len()].copy_from_slice(&bytes);
+            }
+        )*
+    };
+}
+
+// 写入调用方提供的定长缓冲区，空间不足时返回错误而不是扩容
+pub struct FixedOutputByteStream<'a> {
+    buf: &'a mut [u8],
+    head: usize,
+    pub endianness: Endianness,
+    length_prefix: LengthPrefix,
+}
+
+impl<'a> FixedOutputByteStream<'a> {
+    pub fn new(buf: &'a mut [u8], endianness: Endianness) -> FixedOutputByteStream<'a> {
+        FixedOutputByteStream::with_config(buf, &StreamConfig::new().endianness(endianness))
+    }
+
+    // 容量由 buf 决定，配置了 max_size 时只使用 buf 的前 max_size 个字节
+    pub fn with_config(buf: &'a mut [u8], config: &StreamConfig) -> FixedOutputByteStream<'a> {
+        let len = config.get_max_size().map_or(buf.len(), |max_size| max_size.min(buf.len()));
+        FixedOutputByteStream {
+            buf: &mut buf[..len],
+            head: 0,
+            endianness: config.get_endianness(),
+            length_prefix: config.get_length_prefix(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize { self.buf.len() }
+
+    pub fn remaining(&self) -> usize { self.buf.len() - self.head }
+
+    // 空间不足时不写入任何数据
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.check_space(data.len())?;
+        self.buf[self.head..self.head + data.len()].copy_from_slice(data);
+        self.head += data.len();
+        Ok(())
+    }
+
+    fn check_space(&self, num_bytes: usize) -> Result<()> {
+        if num_bytes > self.remaining() {
+            return Err(StreamError::NotEnoughSpace {
+                requested: num_bytes,
+                offset: self.head,
+                remaining: self.remaining(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn write_raw(&mut self, data: &[u8]) -> Result<()> { self.write(data) }
+
+    pub fn write_u8(&mut self, data: u8) -> Result<()> { self.write(&[data]) }
+    pub fn write_i8(&mut self, data: i8) -> Result<()> { self.write_u8(data as u8) }
+
+    pub fn write_bool(&mut self, data: bool) -> Result<()> { self.write_u8(data as u8) }
+
+    pub fn write_u16(&mut self, data: u16) -> Result<()> { write_endianness!(self, data) }
+    pub fn write_i16(&mut self, data: i16) -> Result<()> { self.write_u16(data as u16) }
+
+    pub fn write_u32(&mut self, data: u32) -> Result<()> { write_endianness!(self, data) }
+    pub fn write_i32(&mut self, data: i32) -> Result<()> { self.write_u32(data as u32) }
+
+    pub fn write_u64(&mut self, data: u64) -> Result<()> { write_endianness!(self, data) }
+    pub fn write_i64(&mut self, data: i64) -> Result<()> { self.write_u64(data as u64) }
+
+    pub fn write_u128(&mut self, data: u128) -> Result<()> { write_endianness!(self, data) }
+    pub fn write_i128(&mut self, data: i128) -> Result<()> { self.write_u128(data as u128) }
+
+    pub fn write_f32(&mut self, data: f32) -> Result<()> { self.write_u32(data.to_bits()) }
+    pub fn write_f64(&mut self, data: f64) -> Result<()> { self.write_u64(data.to_bits()) }
+
+    pub fn write_var_u32(&mut self, data: u32) -> Result<()> { self.write_var_u64(data as u64) }
+    pub fn write_var_u64(&mut self, data: u64) -> Result<()> {
+        let mut bytes = [0u8; 10];
+        let len = stream::encode_var(data, &mut bytes);
+        self.write(&bytes[..len])
+    }
+
+    pub fn write_var_i32(&mut self, data: i32) -> Result<()> {
+        self.write_var_u32(((data << 1) ^ (data >> 31)) as u32)
+    }
+    pub fn write_var_i64(&mut self, data: i64) -> Result<()> {
+        self.write_var_u64(((data << 1) ^ (data >> 63)) as u64)
+    }
+
+    // 按配置的长度前缀写入长度
+    pub fn write_len(&mut self, len: usize) -> Result<()> { StreamWriter::write_len(self, len) }
+
+    // 长度前缀和内容一起检查空间，不会只写入前缀
+    pub fn write_string(&mut self, data: &str) -> Result<()> {
+        stream::check_len(self.length_prefix, data.len())?;
+        self.check_space(stream::len_prefix_size(self.length_prefix, data.len()) + data.len())?;
+        self.write_len(data.len())?;
+        self.write(data.as_bytes())
+    }
+
+    pub fn write_string_var(&mut self, data: &str) -> Result<()> {
+        let mut prefix = [0u8; 10];
+        let len = stream::encode_var(data.len() as u64, &mut prefix);
+        self.check_space(len + data.len())?;
+        self.write(&prefix[..len])?;
+        self.write(data.as_bytes())
+    }
+
+    reserve_patch! {
+        u8 => reserve_u8, write_u8, patch_u8;
+        u16 => reserve_u16, write_u16, patch_u16;
+        u32 => reserve_u32, write_u32, patch_u32;
+        u64 => reserve_u64, write_u64, patch_u64;
+    }
+
+    // 开始一个带 u32 长度前缀的段，段可以嵌套
+    pub fn begin_section(&mut self) -> Result<Section> {
+        Ok(Section { len: self.reserve_u32()? })
+    }
+
+    // 结束段并填入段内数据的字节数，不包含长度前缀本身
+    pub fn end_section(&mut self, section: Section) {
+        let start = section.len.offset() + 4;
+        self.patch_u32(section.len, (self.head - start) as u32);
+    }
+
+    pub fn buffer(&self) -> &[u8] { &self.buf[0..self.head] }
+
+    // 结束写入，返回原缓冲区中已写入的部分
+    pub fn finish(self) -> &'a mut [u8] {
+        &mut self.buf[0..self.head]
+    }
+}
+
+impl<'a> StreamWriter for FixedOutputByteStream<'a> {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.length_prefix }
+
+    fn check_space(&self, num_bytes: usize) -> Result<()> { FixedOutputByteStream::check_space(self, num_bytes) }
+
+    fn write_bool(&mut self, value: bool) -> Result<()> { FixedOutputByteStream::write_bool(self, value) }
+
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> { FixedOutputByteStream::write_raw(self, data) }
+
+    fn write_u8(&mut self, value: u8) -> Result<()> { FixedOutputByteStream::write_u8(self, value) }
+    fn write_u16(&mut self, value: u16) -> Result<()> { FixedOutputByteStream::write_u16(self, value) }
+    fn write_u32(&mut self, value: u32) -> Result<()> { FixedOutputByteStream::write_u32(self, value) }
+    fn write_u64(&mut self, value: u64) -> Result<()> { FixedOutputByteStream::write_u64(self, value) }
+    fn write_u128(&mut self, value: u128) -> Result<()> { FixedOutputByteStream::write_u128(self, value) }
+
+    fn write_var_u32(&mut self, value: u32) -> Result<()> { FixedOutputByteStream::write_var_u32(self, value) }
+    fn write_var_u64(&mut self, value: u64) -> Result<()> { FixedOutputByteStream::write_var_u64(self, value) }
+
+    fn write_string(&mut self, data: &str) -> Result<()> { FixedOutputByteStream::write_string(self, data) }
+    fn write_string_var(&mut self, data: &str) -> Result<()> { FixedOutputByteStream::write_string_var(self, data) }
+}
@@ -1,80 +1,358 @@
-use std::mem::{size_of, transmute};
+use std::io::SeekFrom;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::byte_stream::{Endianness, get_platform_endianness};
 use crate::byte_stream::swap_bytes::*;
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::{Result, StreamError};
+use crate::stream;
+use crate::stream::{StreamReader, StringPadding};
 
 pub struct InputByteStream<'a> {
     buf: &'a [u8],
     head: usize,
     endianness: Endianness,
+    config: StreamConfig,
+    // 解码字符串和集合已经分配的字节数，和 take 出来的子流共享。只在配置了 max_alloc 时创建
+    allocated: Option<Arc<AtomicUsize>>,
 }
 
 impl<'a> InputByteStream<'a> {
     pub fn new(buf: &'a [u8], endianness: Endianness) -> InputByteStream<'a> {
+        InputByteStream::with_config(buf, &StreamConfig::new().endianness(endianness))
+    }
+
+    pub fn with_config(buf: &'a [u8], config: &StreamConfig) -> InputByteStream<'a> {
         InputByteStream {
             buf,
             head: 0,
-            endianness,
+            endianness: config.get_endianness(),
+            config: *config,
+            allocated: config.get_max_alloc().map(|_| Arc::new(AtomicUsize::new(0))),
         }
     }
 
-    fn read<T>(&mut self) -> T {
-        let num_bytes = size_of::<T>();
+    pub fn position(&self) -> usize { self.head }
+
+    pub fn remaining(&self) -> usize { self.buf.len() - self.head }
+
+    pub fn is_empty(&self) -> bool { self.remaining() == 0 }
+
+    // 移动读取位置，可以移动到数据末尾，超出范围时返回错误且不移动读取位置
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => self.buf.len() as i128 + offset as i128,
+            SeekFrom::Current(offset) => self.head as i128 + offset as i128,
+        };
+        if position < 0 || position > self.buf.len() as i128 {
+            return Err(StreamError::SeekOutOfBounds {
+                position: position.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+                len: self.buf.len(),
+            });
+        }
+        self.head = position as usize;
+        Ok(self.head)
+    }
+
+    pub fn skip(&mut self, num_bytes: usize) -> Result<()> {
+        self.read_bytes(num_bytes)?;
+        Ok(())
+    }
+
+    // 返回只能读取接下来 num_bytes 个字节的流，当前流移动到这些数据之后，不复制数据
+    pub fn take(&mut self, num_bytes: usize) -> Result<InputByteStream<'a>> {
+        let bytes = self.read_bytes(num_bytes)?;
+        Ok(InputByteStream {
+            buf: bytes,
+            head: 0,
+            endianness: self.endianness,
+            config: self.config,
+            allocated: self.allocated.clone(),
+        })
+    }
+
+    // 进入 begin_section 写入的段，返回只能读取段内数据的流，当前流移动到段之后
+    pub fn enter_section(&mut self) -> Result<InputByteStream<'a>> {
+        let head = self.head;
+        let len = self.read_u32()? as usize;
+        self.take(len).inspect_err(|_| self.head = head)
+    }
+
+    pub fn skip_section(&mut self) -> Result<()> {
+        self.enter_section()?;
+        Ok(())
+    }
+
+    // 读取后恢复读取位置
+    fn peek<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let head = self.head;
+        let result = read(self);
+        self.head = head;
+        result
+    }
+
+    pub fn peek_u8(&mut self) -> Result<u8> { self.peek(Self::read_u8) }
+    pub fn peek_u16(&mut self) -> Result<u16> { self.peek(Self::read_u16) }
+    pub fn peek_u32(&mut self) -> Result<u32> { self.peek(Self::read_u32) }
+
+    fn check_bytes(&self, num_bytes: usize) -> Result<()> {
+        let remaining = self.remaining();
+        if num_bytes > remaining {
+            return Err(StreamError::NotEnoughBytes {
+                requested: num_bytes,
+                offset: self.head,
+                remaining,
+            });
+        }
+        Ok(())
+    }
+
+    // 读取 num_bytes 个字节，剩余数据不足时返回错误且不移动读取位置
+    fn read_bytes(&mut self, num_bytes: usize) -> Result<&'a [u8]> {
+        self.check_bytes(num_bytes)?;
         let bytes = &self.buf[self.head..self.head + num_bytes];
         self.head += num_bytes;
-        unsafe {
-            std::ptr::read(bytes.as_ptr() as *const _)
-        }
+        Ok(bytes)
+    }
+
+    // 返回输入数据中接下来 len 个字节的切片，不复制数据
+    pub fn read_byte_slice(&mut self, len: usize) -> Result<&'a [u8]> { self.read_bytes(len) }
+
+    // 填满 out，剩余数据不足时返回错误且不移动读取位置
+    pub fn read_raw(&mut self, out: &mut [u8]) -> Result<()> {
+        out.copy_from_slice(self.read_bytes(out.len())?);
+        Ok(())
+    }
+
+    // 以平台字节序读取 N 个字节，按字节复制，不要求对齐
+    fn read<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.read_bytes(N)?;
+        Ok(bytes.try_into().unwrap())
     }
 
-    pub fn read_u8(&mut self) -> u8 { self.read() }
-    pub fn read_i8(&mut self) -> i8 { self.read() }
+    pub fn read_u8(&mut self) -> Result<u8> { Ok(self.read::<1>()?[0]) }
+    pub fn read_i8(&mut self) -> Result<i8> { Ok(self.read_u8()? as i8) }
 
-    pub fn read_bool(&mut self) -> bool {
-        let byte = self.read_u8();
-        if byte == 0 { false } else { true }
+    // 配置了 strict_bool 时 0 和 1 以外的值返回错误且不移动读取位置
+    pub fn read_bool(&mut self) -> Result<bool> {
+        let offset = self.head;
+        let byte = self.read_u8()?;
+        if byte > 1 && self.config.is_strict_bool() {
+            self.head = offset;
+            return Err(StreamError::InvalidBool { value: byte, offset });
+        }
+        Ok(byte != 0)
     }
 
-    pub fn read_u16(&mut self) -> u16 {
-        let data = self.read();
+    pub fn read_u16(&mut self) -> Result<u16> {
+        let data = u16::from_ne_bytes(self.read()?);
         if self.endianness != get_platform_endianness() {
-            swap_2_bytes(data)
+            Ok(swap_2_bytes(data))
         } else {
-            data
+            Ok(data)
         }
     }
-    pub fn read_i16(&mut self) -> i16 { self.read_u16() as i16 }
+    pub fn read_i16(&mut self) -> Result<i16> { Ok(self.read_u16()? as i16) }
 
-    pub fn read_u32(&mut self) -> u32 {
-        let data = self.read();
+    pub fn read_u32(&mut self) -> Result<u32> {
+        let data = u32::from_ne_bytes(self.read()?);
         if self.endianness != get_platform_endianness() {
-            swap_4_bytes(data)
+            Ok(swap_4_bytes(data))
         } else {
-            data
+            Ok(data)
         }
     }
-    pub fn read_i32(&mut self) -> i32 { self.read_u32() as i32 }
+    pub fn read_i32(&mut self) -> Result<i32> { Ok(self.read_u32()? as i32) }
 
-    pub fn read_u64(&mut self) -> u64 {
-        let data = self.read();
+    pub fn read_u64(&mut self) -> Result<u64> {
+        let data = u64::from_ne_bytes(self.read()?);
         if self.endianness != get_platform_endianness() {
-            swap_8_bytes(data)
+            Ok(swap_8_bytes(data))
         } else {
-            data
+            Ok(data)
         }
     }
-    pub fn read_i64(&mut self) -> i64 { self.read_u64() as i64 }
+    pub fn read_i64(&mut self) -> Result<i64> { Ok(self.read_u64()? as i64) }
 
-    pub fn read_f32(&mut self) -> f32 {
-        unsafe { transmute(self.read_u32()) }
+    pub fn read_u128(&mut self) -> Result<u128> {
+        let data = u128::from_ne_bytes(self.read()?);
+        if self.endianness != get_platform_endianness() {
+            Ok(swap_16_bytes(data))
+        } else {
+            Ok(data)
+        }
     }
+    pub fn read_i128(&mut self) -> Result<i128> { Ok(self.read_u128()? as i128) }
 
-    pub fn read_string(&mut self) -> String {
-        let len = self.read_u32() as usize;
-        let mut bytes = vec![0; len];
-        for i in 0..len {
-            bytes[i] = self.read_u8();
+    // 读取失败时回到变长整数开始的位置
+    fn read_var(&mut self, bits: u32) -> Result<u64> {
+        let start = self.head;
+        let result = stream::read_var(bits, start, || self.read_u8());
+        if result.is_err() {
+            self.head = start;
         }
-        unsafe { String::from_utf8_unchecked(bytes) }
+        result
+    }
+
+    pub fn read_var_u32(&mut self) -> Result<u32> { Ok(self.read_var(32)? as u32) }
+    pub fn read_var_u64(&mut self) -> Result<u64> { self.read_var(64) }
+
+    pub fn read_var_i32(&mut self) -> Result<i32> {
+        let data = self.read_var_u32()?;
+        Ok((data >> 1) as i32 ^ -((data & 1) as i32))
     }
-}
\ No newline at end of file
+
+    pub fn read_var_i64(&mut self) -> Result<i64> {
+        let data = self.read_var_u64()?;
+        Ok((data >> 1) as i64 ^ -((data & 1) as i64))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    // 按配置的长度前缀读取长度
+    pub fn read_len(&mut self) -> Result<usize> { StreamReader::read_len(self) }
+
+    // 在分配内存之前检查长度：不超过配置的限制，也不超过剩余的数据
+    pub fn check_string_len(&mut self, len: usize) -> Result<()> {
+        self.check_bytes(len)?;
+        stream::charge_string(&self.config, self.allocated.as_deref(), len)
+    }
+
+    // 每个元素至少占 min_bits / 8 个字节，向上取整后检查剩余数据
+    pub fn check_collection_len(&mut self, len: usize, elem_size: usize, min_bits: usize) -> Result<()> {
+        self.check_bytes(len.saturating_mul(min_bits).div_ceil(8))?;
+        stream::charge_collection(&self.config, self.allocated.as_deref(), len, elem_size)
+    }
+
+    pub fn allocated(&self) -> usize { self.allocated.as_ref().map_or(0, |allocated| allocated.load(Ordering::Relaxed)) }
+
+    // 开始解码下一个消息前清零，重新计算 max_alloc
+    pub fn reset_allocated(&mut self) {
+        if let Some(allocated) = &self.allocated {
+            allocated.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // 失败时恢复读取位置，长度超出限制或数据不足时不会只消耗长度前缀
+    fn rewind_on_err<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let head = self.head;
+        let allocated = self.allocated();
+        let result = read(self);
+        if result.is_err() {
+            self.head = head;
+            // 已经计入 max_alloc 的字节一起退还，重试时不会重复计算
+            stream::refund_alloc(self.allocated.as_deref(), self.allocated().saturating_sub(allocated));
+        }
+        result
+    }
+
+    // 失败时不移动读取位置
+    pub fn read_string(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?.to_vec();
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    pub fn read_string_var(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_var_u32()? as usize;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?.to_vec();
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    // 和 read_string 相同，但返回输入数据中的切片，不分配内存，只检查 max_string_len。
+    // 失败时不移动读取位置
+    pub fn read_str(&mut self) -> Result<&'a str> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            stream::check_string_limit(&input.config, len)?;
+            let bytes = input.read_bytes(len)?;
+            std::str::from_utf8(bytes).map_err(StreamError::InvalidUtf8)
+        })
+    }
+
+    // 剩余数据中没有 0 字节或者内容不是合法的 UTF-8 时返回错误且不移动读取位置
+    pub fn read_string_nul(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let rest = &input.buf[input.head..];
+            let len = match rest.iter().position(|byte| *byte == 0) {
+                Some(len) => len,
+                None => return Err(StreamError::MissingNulTerminator { len: rest.len() }),
+            };
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?.to_vec();
+            input.head += 1;
+            String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+        })
+    }
+
+    // 非法的 UTF-8 序列替换为 U+FFFD，失败时不移动读取位置
+    pub fn read_string_lossy(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| {
+            let len = input.read_len()?;
+            input.check_string_len(len)?;
+            let bytes = input.read_bytes(len)?;
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        })
+    }
+}
+
+impl<'a> StreamReader for InputByteStream<'a> {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.config.get_length_prefix() }
+
+    fn read_bool(&mut self) -> Result<bool> { InputByteStream::read_bool(self) }
+
+    fn read_raw(&mut self, out: &mut [u8]) -> Result<()> { InputByteStream::read_raw(self, out) }
+
+    fn read_u8(&mut self) -> Result<u8> { InputByteStream::read_u8(self) }
+    fn read_u16(&mut self) -> Result<u16> { InputByteStream::read_u16(self) }
+    fn read_u32(&mut self) -> Result<u32> { InputByteStream::read_u32(self) }
+    fn read_u64(&mut self) -> Result<u64> { InputByteStream::read_u64(self) }
+    fn read_u128(&mut self) -> Result<u128> { InputByteStream::read_u128(self) }
+
+    // 不完整或者非法的 UTF-8 序列不移动读取位置
+    fn read_char(&mut self) -> Result<char> { self.rewind_on_err(stream::read_utf8_char) }
+
+    fn read_var_u32(&mut self) -> Result<u32> { InputByteStream::read_var_u32(self) }
+    fn read_var_u64(&mut self) -> Result<u64> { InputByteStream::read_var_u64(self) }
+
+    fn check_string_len(&mut self, len: usize) -> Result<()> { InputByteStream::check_string_len(self, len) }
+    fn check_collection_len(&mut self, len: usize, elem_size: usize, min_bits: usize) -> Result<()> {
+        InputByteStream::check_collection_len(self, len, elem_size, min_bits)
+    }
+
+    fn read_string(&mut self) -> Result<String> { InputByteStream::read_string(self) }
+    fn read_string_lossy(&mut self) -> Result<String> { InputByteStream::read_string_lossy(self) }
+    fn read_string_var(&mut self) -> Result<String> { InputByteStream::read_string_var(self) }
+    fn read_string_nul(&mut self) -> Result<String> { InputByteStream::read_string_nul(self) }
+
+    // 这些读取没有对应的公开方法，同样在失败时恢复读取位置
+    fn read_string_u8(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_prefixed_string(input, LengthPrefix::U8))
+    }
+    fn read_string_u16(&mut self) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_prefixed_string(input, LengthPrefix::U16))
+    }
+    fn read_string_fixed(&mut self, width: usize, padding: StringPadding) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_fixed_string(input, width, padding))
+    }
+    fn read_string_utf16(&mut self, endianness: Endianness) -> Result<String> {
+        self.rewind_on_err(|input| stream::read_utf16_string(input, endianness))
+    }
+}
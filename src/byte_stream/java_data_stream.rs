@@ -0,0 +1,134 @@
+use crate::byte_stream::input_byte_stream::InputByteStream;
+use crate::byte_stream::output_byte_stream::OutputByteStream;
+use crate::config::LengthPrefix;
+use crate::error::{Result, StreamError};
+use crate::stream::StreamWriter;
+
+// 和 Java DataOutputStream 兼容的写入接口，总是使用大端字节序，不受流的字节序影响
+pub struct JavaDataOutput<'s> {
+    stream: &'s mut OutputByteStream,
+}
+
+impl<'s> JavaDataOutput<'s> {
+    pub fn new(stream: &'s mut OutputByteStream) -> JavaDataOutput<'s> {
+        JavaDataOutput { stream }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        StreamWriter::write_raw(self.stream, data)
+    }
+
+    pub fn write_boolean(&mut self, value: bool) -> Result<()> { self.write(&[value as u8]) }
+    pub fn write_byte(&mut self, value: i8) -> Result<()> { self.write(&value.to_be_bytes()) }
+    pub fn write_short(&mut self, value: i16) -> Result<()> { self.write(&value.to_be_bytes()) }
+    // Java 的 char 是一个 UTF-16 码元
+    pub fn write_char(&mut self, value: u16) -> Result<()> { self.write(&value.to_be_bytes()) }
+    pub fn write_int(&mut self, value: i32) -> Result<()> { self.write(&value.to_be_bytes()) }
+    pub fn write_long(&mut self, value: i64) -> Result<()> { self.write(&value.to_be_bytes()) }
+    pub fn write_float(&mut self, value: f32) -> Result<()> { self.write(&value.to_be_bytes()) }
+    pub fn write_double(&mut self, value: f64) -> Result<()> { self.write(&value.to_be_bytes()) }
+
+    // writeChars：每个 UTF-16 码元写入两个字节，没有长度前缀
+    pub fn write_chars(&mut self, data: &str) -> Result<()> {
+        let bytes: Vec<u8> = data.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        self.write(&bytes)
+    }
+
+    // writeUTF：u16 字节数加 modified UTF-8。
+    // U+0000 写成两个字节，U+FFFF 以上的字符按代理对分别写成三个字节
+    pub fn write_utf(&mut self, data: &str) -> Result<()> {
+        let mut bytes = Vec::with_capacity(data.len() + 2);
+        bytes.extend_from_slice(&[0, 0]);
+        for unit in data.encode_utf16() {
+            match unit {
+                0x0001..=0x007f => bytes.push(unit as u8),
+                0x0000 | 0x0080..=0x07ff => {
+                    bytes.push(0xc0 | (unit >> 6) as u8);
+                    bytes.push(0x80 | (unit & 0x3f) as u8);
+                }
+                _ => {
+                    bytes.push(0xe0 | (unit >> 12) as u8);
+                    bytes.push(0x80 | (unit >> 6 & 0x3f) as u8);
+                    bytes.push(0x80 | (unit & 0x3f) as u8);
+                }
+            }
+        }
+        let len = bytes.len() - 2;
+        if len > LengthPrefix::U16.max_len() {
+            return Err(StreamError::LengthTooLarge { len, max: LengthPrefix::U16.max_len() });
+        }
+        bytes[..2].copy_from_slice(&(len as u16).to_be_bytes());
+        self.write(&bytes)
+    }
+}
+
+// 和 Java DataInputStream 兼容的读取接口，总是使用大端字节序，不受流的字节序影响
+pub struct JavaDataInput<'s, 'a> {
+    stream: &'s mut InputByteStream<'a>,
+}
+
+impl<'s, 'a> JavaDataInput<'s, 'a> {
+    pub fn new(stream: &'s mut InputByteStream<'a>) -> JavaDataInput<'s, 'a> {
+        JavaDataInput { stream }
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.stream.read_raw(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn read_fully(&mut self, out: &mut [u8]) -> Result<()> { self.stream.read_raw(out) }
+
+    // 和 Java 一样，任何非 0 值都是 true
+    pub fn read_boolean(&mut self) -> Result<bool> { Ok(self.read::<1>()?[0] != 0) }
+    pub fn read_byte(&mut self) -> Result<i8> { Ok(i8::from_be_bytes(self.read()?)) }
+    pub fn read_unsigned_byte(&mut self) -> Result<u8> { Ok(self.read::<1>()?[0]) }
+    pub fn read_short(&mut self) -> Result<i16> { Ok(i16::from_be_bytes(self.read()?)) }
+    pub fn read_unsigned_short(&mut self) -> Result<u16> { Ok(u16::from_be_bytes(self.read()?)) }
+    pub fn read_char(&mut self) -> Result<u16> { Ok(u16::from_be_bytes(self.read()?)) }
+    pub fn read_int(&mut self) -> Result<i32> { Ok(i32::from_be_bytes(self.read()?)) }
+    pub fn read_long(&mut self) -> Result<i64> { Ok(i64::from_be_bytes(self.read()?)) }
+    pub fn read_float(&mut self) -> Result<f32> { Ok(f32::from_be_bytes(self.read()?)) }
+    pub fn read_double(&mut self) -> Result<f64> { Ok(f64::from_be_bytes(self.read()?)) }
+
+    // readUTF：拒绝格式错误的 modified UTF-8，代理对组合回 U+FFFF 以上的字符
+    pub fn read_utf(&mut self) -> Result<String> {
+        let len = self.read_unsigned_short()? as usize;
+        self.stream.check_string_len(len)?;
+        let start = self.stream.position();
+        let bytes = self.stream.read_byte_slice(len)?;
+
+        let mut units = Vec::with_capacity(len);
+        let mut i = 0;
+        while i < bytes.len() {
+            let invalid = StreamError::InvalidModifiedUtf8 { offset: start + i };
+            let byte = bytes[i] as u16;
+            let (unit, count) = match byte >> 4 {
+                0x0..=0x7 => (byte, 1),
+                0xc | 0xd => {
+                    let Some(&next) = bytes.get(i + 1) else {
+                        return Err(invalid);
+                    };
+                    if next & 0xc0 != 0x80 {
+                        return Err(invalid);
+                    }
+                    ((byte & 0x1f) << 6 | (next as u16 & 0x3f), 2)
+                }
+                0xe => {
+                    let (Some(&second), Some(&third)) = (bytes.get(i + 1), bytes.get(i + 2)) else {
+                        return Err(invalid);
+                    };
+                    if second & 0xc0 != 0x80 || third & 0xc0 != 0x80 {
+                        return Err(invalid);
+                    }
+                    ((byte & 0x0f) << 12 | (second as u16 & 0x3f) << 6 | (third as u16 & 0x3f), 3)
+                }
+                _ => return Err(invalid),
+            };
+            units.push(unit);
+            i += count;
+        }
+        String::from_utf16(&units).map_err(|_| StreamError::InvalidUtf16)
+    }
+}
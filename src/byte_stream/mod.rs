@@ -1,8 +1,12 @@
+use std::cmp::max;
+
 pub mod output_byte_stream;
 pub mod input_byte_stream;
+pub mod fixed_output_byte_stream;
+pub mod java_data_stream;
 mod swap_bytes;
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Endianness {
     BigEndian,
     LittleEndian,
@@ -17,4 +21,13 @@ pub fn get_platform_endianness() -> Endianness {
     {
         Endianness::BigEndian
     }
-}
\ No newline at end of file
+}
+// 把缓冲区长度扩大到至少 len，已分配的容量够用时直接用满容量，不重新分配
+pub(crate) fn grow_buffer(buf: &mut Vec<u8>, len: usize) {
+    let new_len = if len <= buf.capacity() {
+        buf.capacity()
+    } else {
+        max(buf.len() * 2, len)
+    };
+    buf.resize(new_len, 0);
+}
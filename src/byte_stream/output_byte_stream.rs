@@ -1,15 +1,55 @@
-use std::cmp::max;
-use std::mem;
-use std::mem::transmute;
-use std::ptr::copy_nonoverlapping;
+use std::marker::PhantomData;
 
-use crate::byte_stream::{Endianness, get_platform_endianness};
+use crate::byte_stream::{Endianness, get_platform_endianness, grow_buffer};
 use crate::byte_stream::swap_bytes::*;
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::Result;
+use crate::stream;
+use crate::stream::StreamWriter;
+
+// reserve_* 预留的位置，之后用对应的 patch_* 填入数据
+#[derive(Debug, Clone, Copy)]
+pub struct Slot<T> {
+    pub(crate) offset: usize,
+    pub(crate) _marker: PhantomData<T>,
+}
+
+impl<T> Slot<T> {
+    pub fn offset(&self) -> usize { self.offset }
+}
+
+// begin_section 返回的句柄，传给 end_section 填入段长度
+#[derive(Debug)]
+#[must_use = "a section must be closed with end_section"]
+pub struct Section {
+    pub(crate) len: Slot<u32>,
+}
+
+macro_rules! reserve_patch {
+    ( $( $t:ty => $reserve:ident, $write:ident, $patch:ident; )* ) => {
+        $(
+            pub fn $reserve(&mut self) -> Slot<$t> {
+                let offset = self.head;
+                self.$write(0);
+                Slot { offset, _marker: PhantomData }
+            }
+
+            // 临时移动写入位置到预留处，按流的字节序写入
+            pub fn $patch(&mut self, slot: Slot<$t>, data: $t) {
+                let head = self.head;
+                self.head = slot.offset;
+                self.$write(data);
+                self.head = head;
+            }
+        )*
+    };
+}
 
 pub struct OutputByteStream {
     pub buf: Vec<u8>,
     pub head: usize,
     pub endianness: Endianness,
+    pub length_prefix: LengthPrefix,
 }
 
 impl Default for OutputByteStream {
@@ -18,40 +58,77 @@ impl Default for OutputByteStream {
             buf: vec![0; 1024],
             head: 0,
             endianness: Endianness::BigEndian,
+            length_prefix: LengthPrefix::U32,
         }
     }
 }
 
 impl OutputByteStream {
-    fn write<T>(&mut self, data: &T) {
+    pub fn with_config(config: &StreamConfig) -> OutputByteStream {
+        OutputByteStream {
+            buf: Vec::with_capacity(config.get_capacity()),
+            head: 0,
+            endianness: config.get_endianness(),
+            length_prefix: config.get_length_prefix(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize, endianness: Endianness) -> OutputByteStream {
+        OutputByteStream::with_config(&StreamConfig::new().capacity(capacity).endianness(endianness))
+    }
+
+    // 接着 vec 中已有的数据继续写入，需要复用空间时先调用 clear
+    pub fn from_vec(buf: Vec<u8>, endianness: Endianness) -> OutputByteStream {
+        OutputByteStream {
+            head: buf.len(),
+            buf,
+            ..OutputByteStream::with_capacity(0, endianness)
+        }
+    }
+
+    pub fn capacity(&self) -> usize { self.buf.capacity() }
+
+    // 保证之后再写入 additional 字节不会重新分配内存
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.head + additional;
+        self.buf.reserve(len.saturating_sub(self.buf.len()));
+    }
+
+    // 回到开头重新写入，保留已分配的内存
+    pub fn clear(&mut self) { self.head = 0 }
+
+    // 返回截断到已写入长度的缓冲区，不复制数据
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.buf.truncate(self.head);
+        self.buf
+    }
+
+    fn write(&mut self, data: &[u8]) {
         let buf = &mut self.buf;
-        let num_bytes = mem::size_of::<T>();
+        let num_bytes = data.len();
 
         if self.head + num_bytes > buf.len() {
-            buf.resize(max(buf.len() * 2, self.head + num_bytes), 0);
+            grow_buffer(buf, self.head + num_bytes);
         }
 
-        unsafe {
-            copy_nonoverlapping(
-                data as *const _ as *const u8,
-                self.buf[self.head..].as_mut_ptr(),
-                num_bytes,
-            );
-        }
+        buf[self.head..self.head + num_bytes].copy_from_slice(data);
         self.head += num_bytes;
     }
 
-    pub fn write_u8(&mut self, data: u8) { self.write(&data) }
-    pub fn write_i8(&mut self, data: i8) { self.write(&data) }
+    // 一次复制写入整段数据
+    pub fn write_raw(&mut self, data: &[u8]) { self.write(data) }
 
-    pub fn write_bool(&mut self, data: bool) { self.write(&data) }
+    pub fn write_u8(&mut self, data: u8) { self.write(&[data]) }
+    pub fn write_i8(&mut self, data: i8) { self.write_u8(data as u8) }
+
+    pub fn write_bool(&mut self, data: bool) { self.write_u8(data as u8) }
 
     pub fn write_u16(&mut self, data: u16) {
         let mut data = data;
         if self.endianness != get_platform_endianness() {
             data = swap_2_bytes(data);
         }
-        self.write(&data)
+        self.write(&data.to_ne_bytes())
     }
     pub fn write_i16(&mut self, data: i16) { self.write_u16(data as u16) }
 
@@ -60,7 +137,7 @@ impl OutputByteStream {
         if self.endianness != get_platform_endianness() {
             data = swap_4_bytes(data);
         }
-        self.write(&data)
+        self.write(&data.to_ne_bytes())
     }
     pub fn write_i32(&mut self, data: i32) { self.write_u32(data as u32) }
 
@@ -69,27 +146,143 @@ impl OutputByteStream {
         if self.endianness != get_platform_endianness() {
             data = swap_8_bytes(data);
         }
-        self.write(&data)
+        self.write(&data.to_ne_bytes())
     }
 
     pub fn write_i64(&mut self, data: i64) { self.write_u64(data as u64) }
 
+    pub fn write_u128(&mut self, data: u128) {
+        let mut data = data;
+        if self.endianness != get_platform_endianness() {
+            data = swap_16_bytes(data);
+        }
+        self.write(&data.to_ne_bytes())
+    }
+    pub fn write_i128(&mut self, data: i128) { self.write_u128(data as u128) }
+
     pub fn write_f32(&mut self, data: f32) {
-        unsafe { self.write_u32(transmute(data)) }
+        self.write_u32(data.to_bits())
+    }
+
+    pub fn write_f64(&mut self, data: f64) {
+        self.write_u64(data.to_bits())
     }
 
-    pub fn write_string(&mut self, data: &String) {
-        self.write_u32(data.len() as u32);
-        let bytes = data.as_bytes();
-        for byte in bytes {
-            self.write_u8(*byte);
+    // LEB128，每个字节低 7 位存数据，最高位表示后面是否还有字节
+    pub fn write_var_u32(&mut self, data: u32) { self.write_var_u64(data as u64) }
+
+    pub fn write_var_u64(&mut self, data: u64) {
+        let mut data = data;
+        while data >= 0x80 {
+            self.write_u8(data as u8 | 0x80);
+            data >>= 7;
         }
+        self.write_u8(data as u8)
+    }
+
+    // zigzag 编码，把绝对值小的负数映射成小的正数
+    pub fn write_var_i32(&mut self, data: i32) {
+        self.write_var_u32(((data << 1) ^ (data >> 31)) as u32)
+    }
+
+    pub fn write_var_i64(&mut self, data: i64) {
+        self.write_var_u64(((data << 1) ^ (data >> 63)) as u64)
+    }
+
+    // 按 length_prefix 写入长度，超出前缀的范围时返回错误且不写入任何数据
+    pub fn write_len(&mut self, len: usize) -> Result<()> {
+        stream::check_len(self.length_prefix, len)?;
+        match self.length_prefix {
+            LengthPrefix::U8 => self.write_u8(len as u8),
+            LengthPrefix::U16 => self.write_u16(len as u16),
+            LengthPrefix::U32 => self.write_u32(len as u32),
+            LengthPrefix::VarInt => self.write_var_u32(len as u32),
+        }
+        Ok(())
+    }
+
+    pub fn write_string(&mut self, data: &str) -> Result<()> {
+        self.write_len(data.len())?;
+        self.write(data.as_bytes());
+        Ok(())
     }
 
-    // pub fn write_vec(&mut self, data: Vec<T>) {
-    //     self.write_u32(data.len() as u32);
-    //
-    // }
+    // 使用变长整数作为长度前缀
+    pub fn write_string_var(&mut self, data: &str) {
+        self.write_var_u32(data.len() as u32);
+        self.write(data.as_bytes());
+    }
+
+    reserve_patch! {
+        u8 => reserve_u8, write_u8, patch_u8;
+        u16 => reserve_u16, write_u16, patch_u16;
+        u32 => reserve_u32, write_u32, patch_u32;
+        u64 => reserve_u64, write_u64, patch_u64;
+    }
+
+    // 开始一个带 u32 长度前缀的段，段可以嵌套
+    pub fn begin_section(&mut self) -> Section {
+        Section { len: self.reserve_u32() }
+    }
+
+    // 结束段并填入段内数据的字节数，不包含长度前缀本身
+    pub fn end_section(&mut self, section: Section) {
+        let start = section.len.offset() + 4;
+        self.patch_u32(section.len, (self.head - start) as u32);
+    }
 
     pub fn buffer(&self) -> &[u8] { &self.buf[0..self.head] }
 }
+
+impl StreamWriter for OutputByteStream {
+    fn endianness(&self) -> Endianness { self.endianness }
+    fn set_endianness(&mut self, endianness: Endianness) { self.endianness = endianness }
+
+    fn length_prefix(&self) -> LengthPrefix { self.length_prefix }
+
+    fn write_bool(&mut self, value: bool) -> Result<()> {
+        OutputByteStream::write_bool(self, value);
+        Ok(())
+    }
+
+    fn write_u8(&mut self, value: u8) -> Result<()> {
+        OutputByteStream::write_u8(self, value);
+        Ok(())
+    }
+    fn write_u16(&mut self, value: u16) -> Result<()> {
+        OutputByteStream::write_u16(self, value);
+        Ok(())
+    }
+    fn write_u32(&mut self, value: u32) -> Result<()> {
+        OutputByteStream::write_u32(self, value);
+        Ok(())
+    }
+    fn write_u64(&mut self, value: u64) -> Result<()> {
+        OutputByteStream::write_u64(self, value);
+        Ok(())
+    }
+    fn write_u128(&mut self, value: u128) -> Result<()> {
+        OutputByteStream::write_u128(self, value);
+        Ok(())
+    }
+
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.write(data);
+        Ok(())
+    }
+
+    fn write_var_u32(&mut self, value: u32) -> Result<()> {
+        OutputByteStream::write_var_u32(self, value);
+        Ok(())
+    }
+    fn write_var_u64(&mut self, value: u64) -> Result<()> {
+        OutputByteStream::write_var_u64(self, value);
+        Ok(())
+    }
+
+    fn write_string(&mut self, data: &str) -> Result<()> { OutputByteStream::write_string(self, data) }
+    fn write_string_var(&mut self, data: &str) -> Result<()> {
+        OutputByteStream::write_string_var(self, data);
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 pub fn swap_2_bytes(data: u16) -> u16 {
-    data >> 8 | data << 8
+    data.rotate_left(8)
 }
 
 pub fn swap_4_bytes(data: u32) -> u32 {
@@ -20,6 +20,12 @@ pub fn swap_8_bytes(data: u64) -> u64 {
         data << 56 & 0xFF00_0000_0000_0000
 }
 
+// 高低 8 字节各自翻转后交换位置
+pub fn swap_16_bytes(data: u128) -> u128 {
+    (swap_8_bytes(data as u64) as u128) << 64 |
+        swap_8_bytes((data >> 64) as u64) as u128
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +47,10 @@ mod tests {
         let num = 9000000006854776000u64;
         assert_eq!(swap_8_bytes(num), num.swap_bytes());
     }
+
+    #[test]
+    fn swap_sixteen_bytes() {
+        let num = 0x0102_0304_0506_0708_090A_0B0C_0D0E_0F10u128;
+        assert_eq!(swap_16_bytes(num), num.swap_bytes());
+    }
 }
\ No newline at end of file
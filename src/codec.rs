@@ -0,0 +1,215 @@
+use std::mem::size_of;
+
+use crate::error::Result;
+use crate::stream::{StreamReader, StreamWriter};
+
+#[cfg(feature = "derive")]
+pub use memory_stream_derive::{StreamDecode, StreamEncode};
+
+// 可以写入任意字节流或位流的类型
+pub trait Encode {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()>;
+}
+
+// 可以从任意字节流或位流读取的类型
+pub trait Decode: Sized {
+    // 编码后至少占用的位数，集合在分配内存前用它检查剩余数据。
+    // 位流上的编码最紧凑，字节流上至少占 MIN_BITS / 8 个字节；不确定时保持为 0
+    const MIN_BITS: usize = 0;
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self>;
+}
+
+macro_rules! impl_primitive {
+    ( $( $t:ty => $write:ident, $read:ident, $min_bits:expr; )* ) => {
+        $(
+            impl Encode for $t {
+                fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+                    w.$write(*self)
+                }
+            }
+
+            impl Decode for $t {
+                const MIN_BITS: usize = $min_bits;
+
+                fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+                    r.$read()
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    bool => write_bool, read_bool, 1;
+    u8 => write_u8, read_u8, 8;
+    i8 => write_i8, read_i8, 8;
+    u16 => write_u16, read_u16, 16;
+    i16 => write_i16, read_i16, 16;
+    u32 => write_u32, read_u32, 32;
+    i32 => write_i32, read_i32, 32;
+    u64 => write_u64, read_u64, 64;
+    i64 => write_i64, read_i64, 64;
+    u128 => write_u128, read_u128, 128;
+    i128 => write_i128, read_i128, 128;
+    f32 => write_f32, read_f32, 32;
+    f64 => write_f64, read_f64, 64;
+    char => write_char, read_char, 8;
+}
+
+impl Encode for str {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        w.write_string(self)
+    }
+}
+
+impl Encode for String {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        w.write_string(self)
+    }
+}
+
+// 最短的长度前缀占一个字节
+impl Decode for String {
+    const MIN_BITS: usize = 8;
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        r.read_string()
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        (**self).encode(w)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        (**self).encode(w)
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    const MIN_BITS: usize = T::MIN_BITS;
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        Ok(Box::new(T::decode(r)?))
+    }
+}
+
+// 和字符串一样使用流配置的长度前缀
+impl<T: Encode> Encode for [T] {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        w.write_len(self.len())?;
+        for item in self {
+            item.encode(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        self.as_slice().encode(w)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    const MIN_BITS: usize = 8;
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        let len = r.read_len()?;
+        r.check_collection_len(len, size_of::<T>(), T::MIN_BITS)?;
+        // 长度来自输入数据，不按它预先分配内存
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(r)?);
+        }
+        Ok(items)
+    }
+}
+
+// 定长数组长度已知，不写长度前缀
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        for item in self {
+            item.encode(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    const MIN_BITS: usize = T::MIN_BITS.saturating_mul(N);
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(T::decode(r)?);
+        }
+        match items.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+// 先写一个 bool 表示是否有值
+impl<T: Encode> Encode for Option<T> {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        match self {
+            Some(value) => {
+                w.write_bool(true)?;
+                value.encode(w)
+            }
+            None => w.write_bool(false),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    const MIN_BITS: usize = 1;
+
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        if r.read_bool()? {
+            Ok(Some(T::decode(r)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ( $( ( $( $name:ident ),+ ) )* ) => {
+        $(
+            impl<$( $name: Encode ),+> Encode for ($( $name, )+) {
+                #[allow(non_snake_case)]
+                fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+                    let ($( $name, )+) = self;
+                    $( $name.encode(w)?; )+
+                    Ok(())
+                }
+            }
+
+            impl<$( $name: Decode ),+> Decode for ($( $name, )+) {
+                const MIN_BITS: usize = 0 $( + $name::MIN_BITS )+;
+
+                fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+                    Ok(($( $name::decode(r)?, )+))
+                }
+            }
+        )*
+    };
+}
+
+impl_tuple! {
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+    (A, B, C, D, E, F, G)
+    (A, B, C, D, E, F, G, H)
+}
@@ -0,0 +1,121 @@
+use crate::byte_stream::Endianness;
+
+// 字符串和集合长度前缀的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    U32,
+    VarInt,
+}
+
+impl LengthPrefix {
+    // 这种前缀能表示的最大长度
+    pub fn max_len(&self) -> usize {
+        match self {
+            LengthPrefix::U8 => u8::MAX as usize,
+            LengthPrefix::U16 => u16::MAX as usize,
+            LengthPrefix::U32 | LengthPrefix::VarInt => u32::MAX as usize,
+        }
+    }
+}
+
+// 创建流时使用的配置，四种流都可以通过 with_config 使用同一份配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamConfig {
+    endianness: Endianness,
+    capacity: usize,
+    max_size: Option<usize>,
+    length_prefix: LengthPrefix,
+    strict_bool: bool,
+    strict_padding: bool,
+    max_string_len: Option<usize>,
+    max_collection_len: Option<usize>,
+    max_alloc: Option<usize>,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            endianness: Endianness::BigEndian,
+            capacity: 1024,
+            max_size: None,
+            length_prefix: LengthPrefix::U32,
+            strict_bool: false,
+            strict_padding: false,
+            max_string_len: None,
+            max_collection_len: None,
+            max_alloc: None,
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn new() -> StreamConfig {
+        StreamConfig::default()
+    }
+
+    pub fn endianness(mut self, endianness: Endianness) -> StreamConfig {
+        self.endianness = endianness;
+        self
+    }
+
+    // 可增长输出流初始分配的字节数
+    pub fn capacity(mut self, capacity: usize) -> StreamConfig {
+        self.capacity = capacity;
+        self
+    }
+
+    // 定长输出流最多使用缓冲区的前 max_size 个字节，超出时写入返回错误。
+    // 可增长输出流没有上限，需要限制输出大小时使用 FixedOutputByteStream 或 FixedOutputBitStream
+    pub fn max_size(mut self, max_size: usize) -> StreamConfig {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    // 字符串和集合的长度前缀，write_string_var 总是使用变长整数
+    pub fn length_prefix(mut self, length_prefix: LengthPrefix) -> StreamConfig {
+        self.length_prefix = length_prefix;
+        self
+    }
+
+    // 字节流读取 bool 时拒绝 0 和 1 以外的值
+    pub fn strict_bool(mut self, strict: bool) -> StreamConfig {
+        self.strict_bool = strict;
+        self
+    }
+
+    // 位流 align_to_byte 跳过的填充位必须为 0
+    pub fn strict_padding(mut self, strict: bool) -> StreamConfig {
+        self.strict_padding = strict;
+        self
+    }
+
+    // 输入流读取字符串时允许的最大字节数
+    pub fn max_string_len(mut self, max_len: usize) -> StreamConfig {
+        self.max_string_len = Some(max_len);
+        self
+    }
+
+    // 输入流读取集合时允许的最大元素个数
+    pub fn max_collection_len(mut self, max_len: usize) -> StreamConfig {
+        self.max_collection_len = Some(max_len);
+        self
+    }
+
+    // 一个输入流和它 take 出来的子流解码字符串和集合时一共最多分配的字节数，由 reset_allocated 清零
+    pub fn max_alloc(mut self, max_alloc: usize) -> StreamConfig {
+        self.max_alloc = Some(max_alloc);
+        self
+    }
+
+    pub fn get_endianness(&self) -> Endianness { self.endianness }
+    pub fn get_capacity(&self) -> usize { self.capacity }
+    pub fn get_max_size(&self) -> Option<usize> { self.max_size }
+    pub fn get_length_prefix(&self) -> LengthPrefix { self.length_prefix }
+    pub fn is_strict_bool(&self) -> bool { self.strict_bool }
+    pub fn is_strict_padding(&self) -> bool { self.strict_padding }
+    pub fn get_max_string_len(&self) -> Option<usize> { self.max_string_len }
+    pub fn get_max_collection_len(&self) -> Option<usize> { self.max_collection_len }
+    pub fn get_max_alloc(&self) -> Option<usize> { self.max_alloc }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::str::Utf8Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StreamError {
+    /// 字节流剩余数据不足，`offset` 为读取开始时的字节偏移
+    NotEnoughBytes {
+        requested: usize,
+        offset: usize,
+        remaining: usize,
+    },
+    /// 位流剩余数据不足，`bit_offset` 为读取开始时的位偏移
+    NotEnoughBits {
+        requested: usize,
+        bit_offset: usize,
+        remaining: usize,
+    },
+    /// 定长输出流剩余空间不足，`offset` 为写入开始时的字节偏移
+    NotEnoughSpace {
+        requested: usize,
+        offset: usize,
+        remaining: usize,
+    },
+    /// 定长位输出流剩余空间不足，`bit_offset` 为写入开始时的位偏移
+    NotEnoughBitSpace {
+        requested: usize,
+        bit_offset: usize,
+        remaining: usize,
+    },
+    /// 读取到的字符串不是合法的 UTF-8
+    InvalidUtf8(Utf8Error),
+    /// 变长整数超出目标类型的范围，`offset` 为变长整数开始的偏移
+    VarintOverflow { offset: usize },
+    /// 变长整数使用了多余的字节编码
+    VarintOverlong { offset: usize },
+    /// 移动读取位置超出了数据范围，`position` 为目标位置
+    SeekOutOfBounds { position: i64, len: usize },
+    /// 枚举标签没有对应的成员
+    UnknownVariant { type_name: &'static str, tag: u64 },
+    /// 长度超出了长度前缀能表示的范围
+    LengthTooLarge { len: usize, max: usize },
+    /// 严格模式下读取到 0 和 1 以外的 bool 值
+    InvalidBool { value: u8, offset: usize },
+    /// 严格模式下对齐时跳过的填充位不为 0，`bit_offset` 为填充开始的位偏移
+    NonZeroPadding { bit_offset: usize },
+    /// 字符串长度超过配置的 max_string_len
+    StringTooLong { len: usize, max: usize },
+    /// 集合长度超过配置的 max_collection_len
+    CollectionTooLong { len: usize, max: usize },
+    /// 分配的内存超过配置的 max_alloc，`remaining` 为剩余可分配的字节数
+    AllocationLimit { requested: usize, remaining: usize },
+    /// 读取到数据末尾也没有找到字符串结尾的 0 字节，`len` 为已经读取的字节数
+    MissingNulTerminator { len: usize },
+    /// 以 0 结尾或用 0 填充的字符串中包含 0 字节，`position` 为它在字符串中的位置
+    InteriorNul { position: usize },
+    /// 位流读取位置没有按字节对齐，无法返回字节切片
+    NotByteAligned { bit_offset: usize },
+    /// 读取到的 UTF-16 字符串包含不成对的代理项
+    InvalidUtf16,
+    /// Java readUTF 读取到格式错误的 modified UTF-8，`offset` 为出错字节的偏移
+    InvalidModifiedUtf8 { offset: usize },
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::NotEnoughBytes { requested, offset, remaining } => write!(
+                f,
+                "requested {} bytes at byte offset {}, but only {} bytes remain",
+                requested, offset, remaining
+            ),
+            StreamError::NotEnoughBits { requested, bit_offset, remaining } => write!(
+                f,
+                "requested {} bits at bit offset {}, but only {} bits ({} bytes) remain",
+                requested, bit_offset, remaining, remaining / 8
+            ),
+            StreamError::NotEnoughSpace { requested, offset, remaining } => write!(
+                f,
+                "cannot write {} bytes at byte offset {}, only {} bytes of space remain",
+                requested, offset, remaining
+            ),
+            StreamError::NotEnoughBitSpace { requested, bit_offset, remaining } => write!(
+                f,
+                "cannot write {} bits at bit offset {}, only {} bits of space remain",
+                requested, bit_offset, remaining
+            ),
+            StreamError::InvalidUtf8(err) => write!(f, "invalid utf-8 string: {}", err),
+            StreamError::VarintOverflow { offset } => {
+                write!(f, "varint at offset {} overflows the target type", offset)
+            }
+            StreamError::VarintOverlong { offset } => {
+                write!(f, "varint at offset {} uses an overlong encoding", offset)
+            }
+            StreamError::SeekOutOfBounds { position, len } => {
+                write!(f, "cannot seek to position {} in a stream of length {}", position, len)
+            }
+            StreamError::UnknownVariant { type_name, tag } => {
+                write!(f, "unknown variant tag {} for {}", tag, type_name)
+            }
+            StreamError::LengthTooLarge { len, max } => {
+                write!(f, "length {} does not fit in the length prefix, the maximum is {}", len, max)
+            }
+            StreamError::InvalidBool { value, offset } => {
+                write!(f, "invalid bool value {} at byte offset {}", value, offset)
+            }
+            StreamError::NonZeroPadding { bit_offset } => {
+                write!(f, "non-zero padding bits at bit offset {}", bit_offset)
+            }
+            StreamError::StringTooLong { len, max } => {
+                write!(f, "string length {} exceeds the limit of {} bytes", len, max)
+            }
+            StreamError::CollectionTooLong { len, max } => {
+                write!(f, "collection length {} exceeds the limit of {} elements", len, max)
+            }
+            StreamError::AllocationLimit { requested, remaining } => write!(
+                f,
+                "cannot allocate {} bytes, only {} bytes of the allocation limit remain",
+                requested, remaining
+            ),
+            StreamError::MissingNulTerminator { len } => {
+                write!(f, "missing NUL terminator after reading {} bytes of string", len)
+            }
+            StreamError::InteriorNul { position } => {
+                write!(f, "string contains a NUL byte at position {}", position)
+            }
+            StreamError::NotByteAligned { bit_offset } => {
+                write!(f, "bit offset {} is not aligned to a byte boundary", bit_offset)
+            }
+            StreamError::InvalidUtf16 => write!(f, "invalid utf-16 string: unpaired surrogate"),
+            StreamError::InvalidModifiedUtf8 { offset } => {
+                write!(f, "malformed modified utf-8 input at byte offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::InvalidUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StreamError>;
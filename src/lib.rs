@@ -1,4 +1,6 @@
-#![feature(ptr_to_from_bits)]
-
+pub mod error;
+pub mod config;
+pub mod stream;
+pub mod codec;
 pub mod byte_stream;
 pub mod bit_stream;
@@ -0,0 +1,521 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::byte_stream::Endianness;
+use crate::config::{LengthPrefix, StreamConfig};
+use crate::error::{Result, StreamError};
+
+// 字节流和位流共用的写入接口。写入方法返回 Result，
+// 让容量受限的实现能够报告错误，可增长的流总是返回 Ok
+pub trait StreamWriter {
+    fn endianness(&self) -> Endianness;
+    fn set_endianness(&mut self, endianness: Endianness);
+
+    fn write_bool(&mut self, value: bool) -> Result<()>;
+
+    fn write_u8(&mut self, value: u8) -> Result<()>;
+    fn write_u16(&mut self, value: u16) -> Result<()>;
+    fn write_u32(&mut self, value: u32) -> Result<()>;
+    fn write_u64(&mut self, value: u64) -> Result<()>;
+    fn write_u128(&mut self, value: u128) -> Result<()>;
+
+    // 写入一段不带长度前缀的原始字节
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.check_space(data.len())?;
+        for byte in data {
+            self.write_u8(*byte)?;
+        }
+        Ok(())
+    }
+
+    fn write_i8(&mut self, value: i8) -> Result<()> { self.write_u8(value as u8) }
+    fn write_i16(&mut self, value: i16) -> Result<()> { self.write_u16(value as u16) }
+    fn write_i32(&mut self, value: i32) -> Result<()> { self.write_u32(value as u32) }
+    fn write_i64(&mut self, value: i64) -> Result<()> { self.write_u64(value as u64) }
+    fn write_i128(&mut self, value: i128) -> Result<()> { self.write_u128(value as u128) }
+
+    fn write_f32(&mut self, value: f32) -> Result<()> { self.write_u32(value.to_bits()) }
+    fn write_f64(&mut self, value: f64) -> Result<()> { self.write_u64(value.to_bits()) }
+
+    // 以 UTF-8 编码写入，占 1 到 4 个字节
+    fn write_char(&mut self, value: char) -> Result<()> {
+        let mut bytes = [0u8; 4];
+        self.write_raw(value.encode_utf8(&mut bytes).as_bytes())
+    }
+
+    // 写入 value 的低 bit_count 位。位流按位紧凑写入，
+    // 字节流按流的字节序写入能容纳 bit_count 位的最少字节数
+    fn write_bits(&mut self, value: u64, bit_count: u32) -> Result<()> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        let byte_count = (bit_count as usize).div_ceil(8);
+        // 先检查空间，避免只写入一部分字节
+        self.check_space(byte_count)?;
+        let value = value & u64::MAX.checked_shr(64 - bit_count).unwrap_or(0);
+        if self.endianness() == Endianness::BigEndian {
+            for byte in &value.to_be_bytes()[8 - byte_count..] {
+                self.write_u8(*byte)?;
+            }
+        } else {
+            for byte in &value.to_le_bytes()[..byte_count] {
+                self.write_u8(*byte)?;
+            }
+        }
+        Ok(())
+    }
+    fn write_signed_bits(&mut self, value: i64, bit_count: u32) -> Result<()> {
+        self.write_bits(value as u64, bit_count)
+    }
+
+    fn write_var_u32(&mut self, value: u32) -> Result<()> { self.write_var_u64(value as u64) }
+    fn write_var_u64(&mut self, value: u64) -> Result<()> {
+        let mut value = value;
+        while value >= 0x80 {
+            self.write_u8(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.write_u8(value as u8)
+    }
+    fn write_var_i32(&mut self, value: i32) -> Result<()> {
+        self.write_var_u32(((value << 1) ^ (value >> 31)) as u32)
+    }
+    fn write_var_i64(&mut self, value: i64) -> Result<()> {
+        self.write_var_u64(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    // 字符串和集合使用的长度前缀
+    fn length_prefix(&self) -> LengthPrefix { LengthPrefix::U32 }
+
+    // 写入 num_bytes 个字节之前调用，容量受限的实现在这里返回错误，避免只写入一部分数据
+    fn check_space(&self, _num_bytes: usize) -> Result<()> { Ok(()) }
+
+    // 按 length_prefix 写入长度，超出前缀的范围时返回错误且不写入
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let prefix = self.length_prefix();
+        write_len_with(self, prefix, len)
+    }
+
+    fn write_string(&mut self, data: &str) -> Result<()> {
+        let prefix = self.length_prefix();
+        write_prefixed_string(self, prefix, data)
+    }
+    fn write_string_var(&mut self, data: &str) -> Result<()> {
+        write_prefixed_string(self, LengthPrefix::VarInt, data)
+    }
+
+    // 不使用 length_prefix 配置，固定使用 u8 或 u16 长度前缀
+    fn write_string_u8(&mut self, data: &str) -> Result<()> {
+        write_prefixed_string(self, LengthPrefix::U8, data)
+    }
+    fn write_string_u16(&mut self, data: &str) -> Result<()> {
+        write_prefixed_string(self, LengthPrefix::U16, data)
+    }
+
+    // C 风格字符串，以一个 0 字节结尾，内容中不能有 0
+    fn write_string_nul(&mut self, data: &str) -> Result<()> {
+        if let Some(position) = data.bytes().position(|byte| byte == 0) {
+            return Err(StreamError::InteriorNul { position });
+        }
+        self.check_space(data.len() + 1)?;
+        self.write_raw(data.as_bytes())?;
+        self.write_u8(0)
+    }
+
+    // UTF-16 编码的字符串，按 length_prefix 写入 UTF-16 码元的个数，
+    // 码元的字节序由 endianness 指定，不使用流的字节序
+    fn write_string_utf16(&mut self, data: &str, endianness: Endianness) -> Result<()> {
+        let mut bytes = Vec::with_capacity(data.len() * 2);
+        for unit in data.encode_utf16() {
+            if endianness == Endianness::BigEndian {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        let len = bytes.len() / 2;
+        let prefix = self.length_prefix();
+        check_len(prefix, len)?;
+        self.check_space(len_prefix_size(prefix, len) + bytes.len())?;
+        self.write_len(len)?;
+        self.write_raw(&bytes)
+    }
+
+    // 写入 width 个字节，不足的部分用 padding 填充。
+    // 用 0 填充时内容中不能有 0，用空格填充时内容末尾的空格读取时会被去掉
+    fn write_string_fixed(&mut self, data: &str, width: usize, padding: StringPadding) -> Result<()> {
+        if data.len() > width {
+            return Err(StreamError::StringTooLong { len: data.len(), max: width });
+        }
+        if padding == StringPadding::Zero {
+            if let Some(position) = data.bytes().position(|byte| byte == 0) {
+                return Err(StreamError::InteriorNul { position });
+            }
+        }
+        self.check_space(width)?;
+        self.write_raw(data.as_bytes())?;
+        for _ in data.len()..width {
+            self.write_u8(padding.byte())?;
+        }
+        Ok(())
+    }
+}
+
+// 字节流和位流共用的读取接口
+pub trait StreamReader {
+    fn endianness(&self) -> Endianness;
+    fn set_endianness(&mut self, endianness: Endianness);
+
+    fn read_bool(&mut self) -> Result<bool>;
+
+    fn read_u8(&mut self) -> Result<u8>;
+    fn read_u16(&mut self) -> Result<u16>;
+    fn read_u32(&mut self) -> Result<u32>;
+    fn read_u64(&mut self) -> Result<u64>;
+    fn read_u128(&mut self) -> Result<u128>;
+
+    // 读取 out.len() 个原始字节填满 out
+    fn read_raw(&mut self, out: &mut [u8]) -> Result<()> {
+        for byte in out.iter_mut() {
+            *byte = self.read_u8()?;
+        }
+        Ok(())
+    }
+
+    fn read_i8(&mut self) -> Result<i8> { Ok(self.read_u8()? as i8) }
+    fn read_i16(&mut self) -> Result<i16> { Ok(self.read_u16()? as i16) }
+    fn read_i32(&mut self) -> Result<i32> { Ok(self.read_u32()? as i32) }
+    fn read_i64(&mut self) -> Result<i64> { Ok(self.read_u64()? as i64) }
+    fn read_i128(&mut self) -> Result<i128> { Ok(self.read_u128()? as i128) }
+
+    fn read_f32(&mut self) -> Result<f32> { Ok(f32::from_bits(self.read_u32()?)) }
+    fn read_f64(&mut self) -> Result<f64> { Ok(f64::from_bits(self.read_u64()?)) }
+
+    fn read_char(&mut self) -> Result<char> { read_utf8_char(self) }
+
+    // 与 StreamWriter::write_bits 对应
+    fn read_bits(&mut self, bit_count: u32) -> Result<u64> {
+        assert!(bit_count <= 64, "bit_count must be at most 64, got {}", bit_count);
+        let byte_count = (bit_count as usize).div_ceil(8);
+        let mut bytes = [0u8; 8];
+        let value = if self.endianness() == Endianness::BigEndian {
+            for byte in &mut bytes[8 - byte_count..] {
+                *byte = self.read_u8()?;
+            }
+            u64::from_be_bytes(bytes)
+        } else {
+            for byte in &mut bytes[..byte_count] {
+                *byte = self.read_u8()?;
+            }
+            u64::from_le_bytes(bytes)
+        };
+        // 和 write_bits 一样只保留低 bit_count 位
+        Ok(value & u64::MAX.checked_shr(64 - bit_count).unwrap_or(0))
+    }
+    fn read_signed_bits(&mut self, bit_count: u32) -> Result<i64> {
+        let value = self.read_bits(bit_count)?;
+        if bit_count == 0 {
+            return Ok(0);
+        }
+        let shift = 64 - bit_count;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    fn read_var_u32(&mut self) -> Result<u32>;
+    fn read_var_u64(&mut self) -> Result<u64>;
+    fn read_var_i32(&mut self) -> Result<i32> {
+        let data = self.read_var_u32()?;
+        Ok((data >> 1) as i32 ^ -((data & 1) as i32))
+    }
+    fn read_var_i64(&mut self) -> Result<i64> {
+        let data = self.read_var_u64()?;
+        Ok((data >> 1) as i64 ^ -((data & 1) as i64))
+    }
+
+    fn length_prefix(&self) -> LengthPrefix { LengthPrefix::U32 }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let prefix = self.length_prefix();
+        read_len_with(self, prefix)
+    }
+
+    // 读取长度前缀之后、分配内存之前调用，输入流在这里检查配置的限制和剩余数据
+    fn check_string_len(&mut self, _len: usize) -> Result<()> { Ok(()) }
+    // elem_size 为每个元素占用的内存字节数，min_bits 为每个元素编码后至少占用的位数
+    fn check_collection_len(&mut self, _len: usize, _elem_size: usize, _min_bits: usize) -> Result<()> { Ok(()) }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.read_len()?;
+        self.check_string_len(len)?;
+        let bytes = read_byte_vec(self, len)?;
+        String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+    }
+    fn read_string_lossy(&mut self) -> Result<String> {
+        let len = self.read_len()?;
+        self.check_string_len(len)?;
+        let bytes = read_byte_vec(self, len)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+    fn read_string_var(&mut self) -> Result<String> {
+        let len = self.read_var_u32()? as usize;
+        self.check_string_len(len)?;
+        let bytes = read_byte_vec(self, len)?;
+        String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+    }
+
+    fn read_string_u8(&mut self) -> Result<String> {
+        read_prefixed_string(self, LengthPrefix::U8)
+    }
+    fn read_string_u16(&mut self) -> Result<String> {
+        read_prefixed_string(self, LengthPrefix::U16)
+    }
+
+    // 读取到 0 字节为止，0 字节被读取但不包含在结果中
+    fn read_string_nul(&mut self) -> Result<String> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = match self.read_u8() {
+                Ok(byte) => byte,
+                Err(StreamError::NotEnoughBytes { .. } | StreamError::NotEnoughBits { .. }) => {
+                    return Err(StreamError::MissingNulTerminator { len: bytes.len() });
+                }
+                Err(err) => return Err(err),
+            };
+            if byte == 0 {
+                break;
+            }
+            bytes.push(byte);
+        }
+        String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+    }
+
+    fn read_string_utf16(&mut self, endianness: Endianness) -> Result<String> {
+        read_utf16_string(self, endianness)
+    }
+
+    // 读取 width 个字节并去掉填充：用 0 填充时截断到第一个 0，用空格填充时去掉末尾的空格
+    fn read_string_fixed(&mut self, width: usize, padding: StringPadding) -> Result<String> {
+        read_fixed_string(self, width, padding)
+    }
+}
+
+// write_string_fixed 和 read_string_fixed 使用的填充字节
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringPadding {
+    Zero,
+    Space,
+}
+
+impl StringPadding {
+    pub fn byte(&self) -> u8 {
+        match self {
+            StringPadding::Zero => 0,
+            StringPadding::Space => b' ',
+        }
+    }
+}
+
+fn write_len_with<W: StreamWriter + ?Sized>(writer: &mut W, prefix: LengthPrefix, len: usize) -> Result<()> {
+    check_len(prefix, len)?;
+    match prefix {
+        LengthPrefix::U8 => writer.write_u8(len as u8),
+        LengthPrefix::U16 => writer.write_u16(len as u16),
+        LengthPrefix::U32 => writer.write_u32(len as u32),
+        LengthPrefix::VarInt => writer.write_var_u32(len as u32),
+    }
+}
+
+fn write_prefixed_string<W: StreamWriter + ?Sized>(writer: &mut W, prefix: LengthPrefix, data: &str) -> Result<()> {
+    check_len(prefix, data.len())?;
+    writer.check_space(len_prefix_size(prefix, data.len()) + data.len())?;
+    write_len_with(writer, prefix, data.len())?;
+    writer.write_raw(data.as_bytes())
+}
+
+fn read_len_with<R: StreamReader + ?Sized>(reader: &mut R, prefix: LengthPrefix) -> Result<usize> {
+    match prefix {
+        LengthPrefix::U8 => Ok(reader.read_u8()? as usize),
+        LengthPrefix::U16 => Ok(reader.read_u16()? as usize),
+        LengthPrefix::U32 => Ok(reader.read_u32()? as usize),
+        LengthPrefix::VarInt => Ok(reader.read_var_u32()? as usize),
+    }
+}
+
+// 根据第一个字节确定 UTF-8 编码的长度
+pub(crate) fn read_utf8_char<R: StreamReader + ?Sized>(reader: &mut R) -> Result<char> {
+    let mut bytes = [0u8; 4];
+    bytes[0] = reader.read_u8()?;
+    let len = match bytes[0] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    reader.read_raw(&mut bytes[1..len])?;
+    let text = std::str::from_utf8(&bytes[..len]).map_err(StreamError::InvalidUtf8)?;
+    Ok(text.chars().next().unwrap())
+}
+
+pub(crate) fn read_utf16_string<R: StreamReader + ?Sized>(reader: &mut R, endianness: Endianness) -> Result<String> {
+    let len = reader.read_len()?;
+    reader.check_string_len(len.saturating_mul(2))?;
+    let bytes = read_byte_vec(reader, len * 2)?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| {
+            if endianness == Endianness::BigEndian {
+                u16::from_be_bytes([unit[0], unit[1]])
+            } else {
+                u16::from_le_bytes([unit[0], unit[1]])
+            }
+        })
+        .collect();
+    String::from_utf16(&units).map_err(|_| StreamError::InvalidUtf16)
+}
+
+pub(crate) fn read_prefixed_string<R: StreamReader + ?Sized>(reader: &mut R, prefix: LengthPrefix) -> Result<String> {
+    let len = read_len_with(reader, prefix)?;
+    reader.check_string_len(len)?;
+    let bytes = read_byte_vec(reader, len)?;
+    String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+}
+
+pub(crate) fn read_fixed_string<R: StreamReader + ?Sized>(reader: &mut R, width: usize, padding: StringPadding) -> Result<String> {
+    reader.check_string_len(width)?;
+    let bytes = read_byte_vec(reader, width)?;
+    string_from_fixed(bytes, padding)
+}
+
+fn string_from_fixed(mut bytes: Vec<u8>, padding: StringPadding) -> Result<String> {
+    let len = match padding {
+        StringPadding::Zero => bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len()),
+        StringPadding::Space => bytes.iter().rposition(|byte| *byte != b' ').map_or(0, |i| i + 1),
+    };
+    bytes.truncate(len);
+    String::from_utf8(bytes).map_err(|err| StreamError::InvalidUtf8(err.utf8_error()))
+}
+
+// 长度可能来自不可信的输入，按块读取，分配的内存不会超过实际读到的数据太多
+fn read_byte_vec<R: StreamReader + ?Sized>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 256];
+    while bytes.len() < len {
+        let count = (len - bytes.len()).min(chunk.len());
+        reader.read_raw(&mut chunk[..count])?;
+        bytes.extend_from_slice(&chunk[..count]);
+    }
+    Ok(bytes)
+}
+
+pub(crate) fn check_len(prefix: LengthPrefix, len: usize) -> Result<()> {
+    if len > prefix.max_len() {
+        return Err(StreamError::LengthTooLarge { len, max: prefix.max_len() });
+    }
+    Ok(())
+}
+
+// 只检查 max_string_len，不分配内存的 read_str 也要遵守
+pub(crate) fn check_string_limit(config: &StreamConfig, len: usize) -> Result<()> {
+    if let Some(max) = config.get_max_string_len() {
+        if len > max {
+            return Err(StreamError::StringTooLong { len, max });
+        }
+    }
+    Ok(())
+}
+
+// 检查 max_string_len 和 max_alloc，通过后把 len 计入 allocated
+pub(crate) fn charge_string(config: &StreamConfig, allocated: Option<&AtomicUsize>, len: usize) -> Result<()> {
+    check_string_limit(config, len)?;
+    charge_alloc(config, allocated, len)
+}
+
+pub(crate) fn charge_collection(
+    config: &StreamConfig,
+    allocated: Option<&AtomicUsize>,
+    len: usize,
+    elem_size: usize,
+) -> Result<()> {
+    if let Some(max) = config.get_max_collection_len() {
+        if len > max {
+            return Err(StreamError::CollectionTooLong { len, max });
+        }
+    }
+    charge_alloc(config, allocated, len.saturating_mul(elem_size))
+}
+
+// allocated 由输入流和它 take 出来的子流共享，子流的分配也计入同一个上限。
+// 没有配置 max_alloc 时输入流不创建计数器，这里什么都不做
+fn charge_alloc(config: &StreamConfig, allocated: Option<&AtomicUsize>, bytes: usize) -> Result<()> {
+    let (Some(max), Some(allocated)) = (config.get_max_alloc(), allocated) else {
+        return Ok(());
+    };
+    allocated
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            (bytes <= max.saturating_sub(current)).then(|| current + bytes)
+        })
+        .map(|_| ())
+        .map_err(|current| StreamError::AllocationLimit {
+            requested: bytes,
+            remaining: max.saturating_sub(current),
+        })
+}
+
+// 读取失败回退时退还这次读取计入的字节数
+pub(crate) fn refund_alloc(allocated: Option<&AtomicUsize>, bytes: usize) {
+    if let Some(allocated) = allocated {
+        let _ = allocated.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            Some(current.saturating_sub(bytes))
+        });
+    }
+}
+
+// 按 prefix 写入长度 len 需要的字节数
+pub(crate) fn len_prefix_size(prefix: LengthPrefix, len: usize) -> usize {
+    match prefix {
+        LengthPrefix::U8 => 1,
+        LengthPrefix::U16 => 2,
+        LengthPrefix::U32 => 4,
+        LengthPrefix::VarInt => var_len(len as u64),
+    }
+}
+
+// LEB128 编码 value 需要的字节数
+pub(crate) fn var_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+// 把 value 编码为 LEB128 写入 out，返回使用的字节数
+pub(crate) fn encode_var(value: u64, out: &mut [u8; 10]) -> usize {
+    let mut value = value;
+    let mut len = 0;
+    while value >= 0x80 {
+        out[len] = value as u8 | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    out[len] = value as u8;
+    len + 1
+}
+
+// 解码最多 bits 位的 LEB128 变长整数，拒绝超出范围和多余字节的编码。
+// offset 为变长整数开始的位置，只用于错误信息
+pub(crate) fn read_var(bits: u32, offset: usize, mut next: impl FnMut() -> Result<u8>) -> Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = next()?;
+        let data = (byte & 0x7f) as u64;
+        if shift + 7 > bits && data >> (bits - shift) != 0 {
+            return Err(StreamError::VarintOverflow { offset });
+        }
+        value |= data << shift;
+        if byte & 0x80 == 0 {
+            if byte == 0 && shift > 0 {
+                return Err(StreamError::VarintOverlong { offset });
+            }
+            return Ok(value);
+        }
+        shift += 7;
+        if shift >= bits {
+            return Err(StreamError::VarintOverflow { offset });
+        }
+    }
+}
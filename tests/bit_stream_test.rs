@@ -1,8 +1,13 @@
+#![allow(clippy::unnecessary_to_owned)]
+
 use std::default::Default;
+use std::io::SeekFrom;
 
+use memory_stream::bit_stream::fixed_output_bit_stream::FixedOutputBitStream;
 use memory_stream::bit_stream::input_bit_stream::InputBitStream;
 use memory_stream::bit_stream::output_bit_stream::OutputBitStream;
 use memory_stream::byte_stream::Endianness;
+use memory_stream::error::StreamError;
 
 #[test]
 fn write_read_one_byte() {
@@ -15,11 +20,11 @@ fn write_read_one_byte() {
     assert_eq!(o.buffer(), [1, 252, 251, 3]);
 
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!(i.read_bool(), true);
-    assert_eq!(i.read_bool(), false);
-    assert_eq!(i.read_u8(), 0);
-    assert_eq!(i.read_u8(), 255);
-    assert_eq!(i.read_i8(), -2i8);
+    assert!(i.read_bool().unwrap());
+    assert!(!i.read_bool().unwrap());
+    assert_eq!(i.read_u8().unwrap(), 0);
+    assert_eq!(i.read_u8().unwrap(), 255);
+    assert_eq!(i.read_i8().unwrap(), -2i8);
 }
 
 #[test]
@@ -31,10 +36,10 @@ fn write_read_two_bytes() {
     o.write_i16(-2i16);
 
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!(i.read_u16(), 0);
-    assert_eq!(i.read_u16(), 100);
-    assert_eq!(i.read_u16(), 65535);
-    assert_eq!(i.read_i16(), -2i16);
+    assert_eq!(i.read_u16().unwrap(), 0);
+    assert_eq!(i.read_u16().unwrap(), 100);
+    assert_eq!(i.read_u16().unwrap(), 65535);
+    assert_eq!(i.read_i16().unwrap(), -2i16);
 }
 
 #[test]
@@ -46,10 +51,10 @@ fn write_read_four_bytes() {
     o.write_i32(-2i32);
 
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!(i.read_u32(), 0);
-    assert_eq!(i.read_u32(), 100);
-    assert_eq!(i.read_u32(), 4294967295);
-    assert_eq!(i.read_i32(), -2i32);
+    assert_eq!(i.read_u32().unwrap(), 0);
+    assert_eq!(i.read_u32().unwrap(), 100);
+    assert_eq!(i.read_u32().unwrap(), 4294967295);
+    assert_eq!(i.read_i32().unwrap(), -2i32);
 }
 
 #[test]
@@ -61,10 +66,36 @@ fn write_read_eight_bytes() {
     o.write_i64(-2i64);
 
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!(i.read_u64(), 0);
-    assert_eq!(i.read_u64(), 100);
-    assert_eq!(i.read_u64(), 17446744073709552000);
-    assert_eq!(i.read_i64(), -2i64);
+    assert_eq!(i.read_u64().unwrap(), 0);
+    assert_eq!(i.read_u64().unwrap(), 100);
+    assert_eq!(i.read_u64().unwrap(), 17446744073709552000);
+    assert_eq!(i.read_i64().unwrap(), -2i64);
+}
+
+#[test]
+fn write_read_sixteen_bytes() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputBitStream {
+            endianness,
+            ..Default::default()
+        };
+        o.write_u128(0u128);
+        o.write_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        o.write_u128(u128::MAX);
+        o.write_i128(-2i128);
+        o.write_i128(i128::MIN);
+
+        let mut i = InputBitStream::new(o.buffer());
+        i.endianness = endianness;
+        assert_eq!(i.read_u128().unwrap(), 0);
+        assert_eq!(i.read_u128().unwrap(), 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        assert_eq!(i.read_u128().unwrap(), u128::MAX);
+        assert_eq!(i.read_i128().unwrap(), -2i128);
+        assert_eq!(i.read_i128().unwrap(), i128::MIN);
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
 }
 
 #[test]
@@ -76,17 +107,41 @@ fn write_read_f32() {
     o.write_f32(-1.234);
     o.write_f32(f32::MAX);
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!(1.234, i.read_f32());
-    assert_eq!(-1.234, i.read_f32());
-    assert_eq!(f32::MAX, i.read_f32());
+    assert_eq!(1.234, i.read_f32().unwrap());
+    assert_eq!(-1.234, i.read_f32().unwrap());
+    assert_eq!(f32::MAX, i.read_f32().unwrap());
+}
+
+#[test]
+fn write_read_f64() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputBitStream {
+            endianness,
+            ..Default::default()
+        };
+        o.write_f64(1.234);
+        o.write_f64(-1.234);
+        o.write_f64(f64::MAX);
+        o.write_f64(f64::MIN_POSITIVE);
+
+        let mut i = InputBitStream::new(o.buffer());
+        i.endianness = endianness;
+        assert_eq!(1.234, i.read_f64().unwrap());
+        assert_eq!(-1.234, i.read_f64().unwrap());
+        assert_eq!(f64::MAX, i.read_f64().unwrap());
+        assert_eq!(f64::MIN_POSITIVE, i.read_f64().unwrap());
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
 }
 
 #[test]
 fn write_read_string() {
     let mut o = OutputBitStream::default();
-    o.write_string(&"hello world!".to_string());
+    o.write_string(&"hello world!".to_string()).unwrap();
     let mut i = InputBitStream::new(o.buffer());
-    assert_eq!("hello world!", i.read_string().as_str());
+    assert_eq!("hello world!", i.read_string().unwrap().as_str());
 }
 
 #[test]
@@ -102,22 +157,454 @@ fn write_read_all() {
         o.write_i32(65536);
         o.write_i64(-5611626018427388000);
         o.write_f32(123.456);
-        o.write_string(&"hello world!".to_string());
+        o.write_string(&"hello world!".to_string()).unwrap();
+
+        let mut i = InputBitStream::new(o.buffer());
+        i.endianness = endianness;
+        assert!(i.read_bool().unwrap());
+        assert_eq!(127, i.read_i8().unwrap());
+        assert_eq!(30000, i.read_i16().unwrap());
+        assert_eq!(65536, i.read_i32().unwrap());
+        assert_eq!(-5611626018427388000, i.read_i64().unwrap());
+        assert_eq!(123.456, i.read_f32().unwrap());
+        assert_eq!("hello world!", i.read_string().unwrap().as_str());
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
+}
+
+#[test]
+fn read_past_end() {
+    let mut o = OutputBitStream::default();
+    o.write_bool(true);
+    o.write_u8(200);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    assert_eq!(
+        i.read_u16(),
+        Err(StreamError::NotEnoughBits { requested: 16, bit_offset: 1, remaining: 15 })
+    );
+    // 失败的读取不移动读取位置
+    assert_eq!(i.read_u8().unwrap(), 200);
+    assert!(i.read_bool().is_ok());
+    assert!(i.read_u8().is_err());
+}
+
+#[test]
+fn read_truncated_string() {
+    let mut o = OutputBitStream::default();
+    o.write_string(&"hello world!".to_string()).unwrap();
+
+    let mut i = InputBitStream::new(&o.buffer()[..8]);
+    assert_eq!(
+        i.read_string(),
+        Err(StreamError::NotEnoughBits { requested: 96, bit_offset: 32, remaining: 32 })
+    );
+}
+
+#[test]
+fn read_invalid_utf8_string() {
+    let mut o = OutputBitStream::default();
+    o.write_bool(true);
+    o.write_u32(4);
+    for byte in [b'a', 0xff, b'b', 0xc3] {
+        o.write_u8(byte);
+    }
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    match i.read_string() {
+        Err(StreamError::InvalidUtf8(err)) => assert_eq!(err.valid_up_to(), 1),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_string_lossy().unwrap(), "a\u{fffd}b\u{fffd}");
+}
+
+#[test]
+fn write_across_buffer_growth() {
+    let mut o = OutputBitStream::default();
+    let len = o.buf.len();
+    for _ in 0..len {
+        o.write_u8(0xaa);
+    }
+    o.write_bool(true);
+    o.write_u16(0x1234);
+
+    let mut i = InputBitStream::new(o.buffer());
+    for _ in 0..len {
+        assert_eq!(i.read_u8().unwrap(), 0xaa);
+    }
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_u16().unwrap(), 0x1234);
+}
+
+#[test]
+fn write_read_bits() {
+    let mut o = OutputBitStream::default();
+    o.write_bits(5, 3);
+    o.write_bits(17, 5);
+    o.write_bits(0x7ff, 11);
+    o.write_bits(0x1abcd, 17);
+    o.write_bits(u64::MAX, 64);
+    o.write_bits(0xff, 4);
+    o.write_bits(1, 0);
+    assert_eq!(o.buffer().len(), 13);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(i.read_bits(3).unwrap(), 5);
+    assert_eq!(i.read_bits(5).unwrap(), 17);
+    assert_eq!(i.read_bits(11).unwrap(), 0x7ff);
+    assert_eq!(i.read_bits(17).unwrap(), 0x1abcd);
+    assert_eq!(i.read_bits(64).unwrap(), u64::MAX);
+    assert_eq!(i.read_bits(4).unwrap(), 0xf);
+    assert_eq!(i.read_bits(0).unwrap(), 0);
+    assert_eq!(
+        i.read_bits(5),
+        Err(StreamError::NotEnoughBits { requested: 5, bit_offset: 104, remaining: 0 })
+    );
+}
+
+#[test]
+fn write_read_signed_bits() {
+    let mut o = OutputBitStream::default();
+    o.write_signed_bits(-3, 3);
+    o.write_signed_bits(3, 3);
+    o.write_signed_bits(-1024, 11);
+    o.write_signed_bits(65535, 17);
+    o.write_signed_bits(i64::MIN, 64);
 
-        let mut i = InputBitStream {
-            buf: o.buffer(),
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(i.read_signed_bits(3).unwrap(), -3);
+    assert_eq!(i.read_signed_bits(3).unwrap(), 3);
+    assert_eq!(i.read_signed_bits(11).unwrap(), -1024);
+    assert_eq!(i.read_signed_bits(17).unwrap(), 65535);
+    assert_eq!(i.read_signed_bits(64).unwrap(), i64::MIN);
+}
+
+#[test]
+fn write_read_varint() {
+    let mut o = OutputBitStream::default();
+    o.write_bool(true);
+    o.write_var_u32(300);
+    o.write_var_u64(u64::MAX);
+    o.write_var_i32(-1);
+    o.write_var_i64(i64::MIN);
+    o.write_string_var("hello world!");
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_var_u32().unwrap(), 300);
+    assert_eq!(i.read_var_u64().unwrap(), u64::MAX);
+    assert_eq!(i.read_var_i32().unwrap(), -1);
+    assert_eq!(i.read_var_i64().unwrap(), i64::MIN);
+    assert_eq!(i.read_string_var().unwrap(), "hello world!");
+
+    let overlong = [0x80, 0x00];
+    let mut i = InputBitStream::new(&overlong);
+    assert_eq!(i.read_var_u32(), Err(StreamError::VarintOverlong { offset: 0 }));
+    assert_eq!(i.read_u8().unwrap(), 0x80);
+}
+
+#[test]
+fn cursor_control() {
+    let mut o = OutputBitStream::default();
+    o.write_bits(5, 3);
+    o.write_bits(0x2a, 6);
+    o.write_u8(0xbc);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(i.remaining_bits(), 24);
+    assert_eq!(i.peek_bits(3).unwrap(), 5);
+    assert_eq!(i.peek_bits(9).unwrap(), 0x2a << 3 | 5);
+    assert_eq!(i.bit_position(), 0);
+
+    i.skip_bits(3).unwrap();
+    assert_eq!(i.read_bits(6).unwrap(), 0x2a);
+    assert_eq!(i.bit_position(), 9);
+    i.align_to_byte().unwrap();
+    assert_eq!(i.bit_position(), 16);
+    i.align_to_byte().unwrap();
+    assert_eq!(i.bit_position(), 16);
+
+    assert_eq!(i.seek_bits(SeekFrom::Current(-7)).unwrap(), 9);
+    assert_eq!(i.read_u8().unwrap(), 0xbc);
+    assert_eq!(i.seek_bits(SeekFrom::Start(3)).unwrap(), 3);
+    assert_eq!(i.read_bits(6).unwrap(), 0x2a);
+    assert_eq!(i.seek_bits(SeekFrom::End(0)).unwrap(), 24);
+    assert_eq!(i.remaining_bits(), 0);
+    assert!(i.peek_bits(1).is_err());
+
+    assert_eq!(
+        i.seek_bits(SeekFrom::End(1)),
+        Err(StreamError::SeekOutOfBounds { position: 25, len: 24 })
+    );
+    i.seek_bits(SeekFrom::Start(20)).unwrap();
+    assert_eq!(
+        i.skip_bits(5),
+        Err(StreamError::NotEnoughBits { requested: 5, bit_offset: 20, remaining: 4 })
+    );
+    assert_eq!(i.bit_position(), 20);
+}
+
+#[test]
+fn finish_with_bit_len() {
+    let mut o = OutputBitStream::default();
+    o.write_bits(5, 3);
+    o.write_bool(true);
+    assert_eq!(o.bit_len(), 4);
+    o.align_to_byte();
+    assert_eq!(o.bit_len(), 8);
+    o.align_to_byte();
+    assert_eq!(o.bit_len(), 8);
+    o.write_bits(0x7ff, 11);
+    o.pad_to_bits(21);
+    assert_eq!(o.bit_len(), 21);
+    o.write_bits(0b101, 3);
+
+    let (bytes, bit_len) = o.finish();
+    assert_eq!(bit_len, 24);
+    assert_eq!(bytes, [0x0d, 0xff, 0xa7]);
+
+    let mut o = OutputBitStream::default();
+    o.write_bits(0x1abcd, 17);
+    let (bytes, bit_len) = o.finish();
+    assert_eq!((bytes.len(), bit_len), (3, 17));
+
+    let mut i = InputBitStream::with_bit_len(&bytes, bit_len).unwrap();
+    assert_eq!(i.bit_len(), 17);
+    assert_eq!(i.read_bits(16).unwrap(), 0xabcd);
+    assert_eq!(i.remaining_bits(), 1);
+    assert_eq!(
+        i.read_bits(2),
+        Err(StreamError::NotEnoughBits { requested: 2, bit_offset: 16, remaining: 1 })
+    );
+    assert_eq!(i.read_bits(1).unwrap(), 1);
+    assert!(i.align_to_byte().is_err());
+
+    assert_eq!(
+        InputBitStream::with_bit_len(&bytes, 25).err(),
+        Some(StreamError::NotEnoughBits { requested: 25, bit_offset: 0, remaining: 24 })
+    );
+}
+
+#[test]
+fn reserve_and_patch() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputBitStream {
             endianness,
             ..Default::default()
         };
-        assert_eq!(true, i.read_bool());
-        assert_eq!(127, i.read_i8());
-        assert_eq!(30000, i.read_i16());
-        assert_eq!(65536, i.read_i32());
-        assert_eq!(-5611626018427388000, i.read_i64());
-        assert_eq!(123.456, i.read_f32());
-        assert_eq!("hello world!", i.read_string().as_str());
+        o.write_bits(0b101, 3);
+        let count = o.reserve_bits(5);
+        o.write_bool(true);
+        let len = o.reserve_u16();
+        o.write_bits(0x7f, 7);
+        let wide = o.reserve_bits(13);
+        o.write_bits(0b11, 2);
+
+        o.patch_bits(count, 0b10110);
+        o.patch_u16(len, 0x1234);
+        o.patch_bits(wide, 0x1abc);
+        assert_eq!((count.bit_offset(), count.bit_count()), (3, 5));
+        assert_eq!((wide.bit_offset(), wide.bit_count()), (32, 13));
+
+        let mut i = InputBitStream::new(o.buffer());
+        i.endianness = endianness;
+        assert_eq!(i.read_bits(3).unwrap(), 0b101);
+        assert_eq!(i.read_bits(5).unwrap(), 0b10110);
+        assert!(i.read_bool().unwrap());
+        assert_eq!(i.read_u16().unwrap(), 0x1234);
+        assert_eq!(i.read_bits(7).unwrap(), 0x7f);
+        assert_eq!(i.read_bits(13).unwrap(), 0x1abc);
+        assert_eq!(i.read_bits(2).unwrap(), 0b11);
     }
 
     t(Endianness::LittleEndian);
     t(Endianness::BigEndian);
 }
+
+#[test]
+fn take_sub_stream() {
+    let mut o = OutputBitStream::default();
+    o.write_bits(0b101, 3);
+    o.write_bits(0x1abc, 13);
+    o.write_bits(0b11, 2);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(i.read_bits(3).unwrap(), 0b101);
+    let mut payload = i.take(13).unwrap();
+    assert_eq!(i.bit_position(), 16);
+    assert_eq!(payload.bit_position(), 0);
+    assert_eq!(payload.bit_len(), 13);
+    assert_eq!(payload.peek_bits(13).unwrap(), 0x1abc);
+    assert_eq!(payload.read_bits(12).unwrap(), 0xabc);
+    assert_eq!(
+        payload.read_bits(2),
+        Err(StreamError::NotEnoughBits { requested: 2, bit_offset: 12, remaining: 1 })
+    );
+    // 子流不能移动到窗口之外
+    assert_eq!(payload.seek_bits(SeekFrom::Start(0)).unwrap(), 0);
+    assert!(payload.seek_bits(SeekFrom::Current(-1)).is_err());
+    assert!(payload.seek_bits(SeekFrom::End(1)).is_err());
+    assert_eq!(payload.read_bits(13).unwrap(), 0x1abc);
+    assert_eq!(i.read_bits(2).unwrap(), 0b11);
+
+    assert!(i.take(8).is_err());
+}
+
+#[test]
+fn fixed_output_stream() {
+    let mut buf = [0xffu8; 4];
+    let mut o = FixedOutputBitStream::new(&mut buf, Endianness::BigEndian);
+    o.write_bits(5, 3).unwrap();
+    o.write_u16(0x1234).unwrap();
+    o.write_signed_bits(-2, 4).unwrap();
+    assert_eq!(o.bit_len(), 23);
+    assert_eq!(o.remaining_bits(), 9);
+
+    assert_eq!(
+        o.write_u16(1),
+        Err(StreamError::NotEnoughBitSpace { requested: 16, bit_offset: 23, remaining: 9 })
+    );
+    assert!(o.write_string_var("a").is_err());
+    assert_eq!(o.bit_len(), 23);
+    o.write_bool(true).unwrap();
+    o.write_bits(3, 2).unwrap();
+
+    let (written, bit_len) = o.finish();
+    assert_eq!(written.len(), 4);
+    assert_eq!(bit_len, 26);
+    // 最后一个字节中未写入的位被清零
+    assert_eq!(written[3] & 0xfc, 0);
+
+    let mut i = InputBitStream::with_bit_len(written, bit_len).unwrap();
+    assert_eq!(i.read_bits(3).unwrap(), 5);
+    assert_eq!(i.read_u16().unwrap(), 0x1234);
+    assert_eq!(i.read_signed_bits(4).unwrap(), -2);
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_bits(2).unwrap(), 3);
+    assert_eq!(i.remaining_bits(), 0);
+}
+
+#[test]
+fn fixed_reserve_patch_and_pad() {
+    let mut buf = [0u8; 5];
+    let mut o = FixedOutputBitStream::new(&mut buf, Endianness::BigEndian);
+    o.write_bits(0b101, 3).unwrap();
+    let count = o.reserve_bits(5).unwrap();
+    let len = o.reserve_u16().unwrap();
+    o.write_bool(true).unwrap();
+    o.pad_to_bits(30).unwrap();
+    assert_eq!(o.bit_len(), 30);
+    o.patch_bits(count, 0b10110);
+    o.patch_u16(len, 0x1234);
+    assert_eq!((count.bit_offset(), count.bit_count()), (3, 5));
+
+    assert!(o.pad_to_bits(41).is_err());
+    assert!(o.reserve_u16().is_err());
+    assert_eq!(o.bit_len(), 30);
+    let wide = o.reserve_bits(10).unwrap();
+    o.patch_bits(wide, 0x3ff);
+
+    let (written, bit_len) = o.finish();
+    let mut i = InputBitStream::with_bit_len(written, bit_len).unwrap();
+    assert_eq!(i.read_bits(3).unwrap(), 0b101);
+    assert_eq!(i.read_bits(5).unwrap(), 0b10110);
+    assert_eq!(i.read_u16().unwrap(), 0x1234);
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_bits(5).unwrap(), 0);
+    assert_eq!(i.read_bits(10).unwrap(), 0x3ff);
+    assert_eq!(i.remaining_bits(), 0);
+}
+
+#[test]
+fn reuse_buffer() {
+    let mut o = OutputBitStream::with_capacity(4, Endianness::BigEndian);
+    assert!(o.capacity() >= 4);
+    o.write_bits(0b101, 3);
+    o.write_u8(0xff);
+    o.clear();
+    assert_eq!(o.bit_len(), 0);
+    o.write_bits(0b1, 1);
+    assert_eq!(o.buffer(), [1]);
+
+    o.reserve(64);
+    let capacity = o.capacity();
+    let ptr = o.buf.as_ptr();
+    for _ in 0..64 {
+        o.write_u8(0xaa);
+    }
+    assert_eq!(o.capacity(), capacity);
+    assert_eq!(o.buf.as_ptr(), ptr);
+
+    let v = o.into_vec();
+    assert_eq!(v.len(), 65);
+
+    let mut o = OutputBitStream::from_vec(vec![0x12, 0x34], Endianness::BigEndian);
+    assert_eq!(o.bit_len(), 16);
+    o.write_bits(0b11, 2);
+    assert_eq!(o.into_vec(), [0x12, 0x34, 0b11]);
+}
+
+#[test]
+fn borrowed_reads() {
+    let mut o = OutputBitStream::default();
+    o.write_string(&"asset".to_string()).unwrap();
+    o.write_bool(true);
+    o.write_u8(7);
+
+    let data = o.buffer();
+    let mut i = InputBitStream::new(data);
+    let name = i.read_str().unwrap();
+    assert_eq!(name, "asset");
+    assert_eq!(name.as_ptr(), data[4..].as_ptr());
+
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_byte_slice(1), Err(StreamError::NotByteAligned { bit_offset: 73 }));
+    assert!(i.read_str().is_err());
+    assert_eq!(i.bit_position(), 73);
+    assert_eq!(i.read_u8().unwrap(), 7);
+    i.align_to_byte().unwrap();
+    assert_eq!(i.read_byte_slice(0).unwrap(), []);
+}
+
+#[test]
+fn raw_bytes() {
+    let blob: Vec<u8> = (0..=255).collect();
+    let mut o = OutputBitStream::with_capacity(0, Endianness::BigEndian);
+    o.write_raw(&blob);
+    o.write_bits(0b101, 3);
+    o.write_raw(&blob);
+    assert_eq!(o.bit_len(), 256 * 8 * 2 + 3);
+
+    let mut i = InputBitStream::new(o.buffer());
+    let mut out = [0u8; 256];
+    i.read_raw(&mut out).unwrap();
+    assert_eq!(out.as_slice(), blob.as_slice());
+    assert_eq!(i.read_bits(3).unwrap(), 0b101);
+    i.read_raw(&mut out).unwrap();
+    assert_eq!(out.as_slice(), blob.as_slice());
+    assert!(i.read_raw(&mut [0u8; 1]).is_err());
+    assert_eq!(i.bit_position(), 256 * 8 * 2 + 3);
+
+    let mut buf = [0u8; 4];
+    let mut fixed = FixedOutputBitStream::new(&mut buf, Endianness::BigEndian);
+    fixed.write_raw(&[0xab]).unwrap();
+    fixed.write_bool(true).unwrap();
+    fixed.write_raw(&[0xcd, 0xef]).unwrap();
+    assert!(fixed.write_raw(&[0x01]).is_err());
+    let (written, bit_len) = fixed.finish();
+    let mut i = InputBitStream::with_bit_len(written, bit_len).unwrap();
+    let mut out = [0u8; 1];
+    i.read_raw(&mut out).unwrap();
+    assert_eq!(out, [0xab]);
+    assert!(i.read_bool().unwrap());
+    let mut out = [0u8; 2];
+    i.read_raw(&mut out).unwrap();
+    assert_eq!(out, [0xcd, 0xef]);
+}
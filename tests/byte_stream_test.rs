@@ -1,6 +1,13 @@
+// 保留传入 &String 的旧写法，确认 write_string 改为接受 &str 后原有调用仍然可以编译
+#![allow(clippy::unnecessary_to_owned)]
+
+use std::io::SeekFrom;
+
 use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::fixed_output_byte_stream::FixedOutputByteStream;
 use memory_stream::byte_stream::input_byte_stream::InputByteStream;
 use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::error::StreamError;
 
 #[test]
 fn write_read_one_byte() {
@@ -13,11 +20,11 @@ fn write_read_one_byte() {
     assert_eq!(o.buffer(), [1, 0, 0, 255, 254]);
 
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!(i.read_bool(), true);
-    assert_eq!(i.read_bool(), false);
-    assert_eq!(i.read_u8(), 0);
-    assert_eq!(i.read_u8(), 255);
-    assert_eq!(i.read_i8(), -2i8);
+    assert!(i.read_bool().unwrap());
+    assert!(!i.read_bool().unwrap());
+    assert_eq!(i.read_u8().unwrap(), 0);
+    assert_eq!(i.read_u8().unwrap(), 255);
+    assert_eq!(i.read_i8().unwrap(), -2i8);
 }
 
 #[test]
@@ -29,10 +36,10 @@ fn write_read_two_bytes() {
     o.write_i16(-2i16);
 
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!(i.read_u16(), 0);
-    assert_eq!(i.read_u16(), 100);
-    assert_eq!(i.read_u16(), 65535);
-    assert_eq!(i.read_i16(), -2i16);
+    assert_eq!(i.read_u16().unwrap(), 0);
+    assert_eq!(i.read_u16().unwrap(), 100);
+    assert_eq!(i.read_u16().unwrap(), 65535);
+    assert_eq!(i.read_i16().unwrap(), -2i16);
 }
 
 
@@ -45,10 +52,10 @@ fn write_read_four_bytes() {
     o.write_i32(-2i32);
 
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!(i.read_u32(), 0);
-    assert_eq!(i.read_u32(), 100);
-    assert_eq!(i.read_u32(), 4294967295);
-    assert_eq!(i.read_i32(), -2i32);
+    assert_eq!(i.read_u32().unwrap(), 0);
+    assert_eq!(i.read_u32().unwrap(), 100);
+    assert_eq!(i.read_u32().unwrap(), 4294967295);
+    assert_eq!(i.read_i32().unwrap(), -2i32);
 }
 
 #[test]
@@ -60,10 +67,35 @@ fn write_read_eight_bytes() {
     o.write_i64(-2i64);
 
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!(i.read_u64(), 0);
-    assert_eq!(i.read_u64(), 100);
-    assert_eq!(i.read_u64(), 17446744073709552000);
-    assert_eq!(i.read_i64(), -2i64);
+    assert_eq!(i.read_u64().unwrap(), 0);
+    assert_eq!(i.read_u64().unwrap(), 100);
+    assert_eq!(i.read_u64().unwrap(), 17446744073709552000);
+    assert_eq!(i.read_i64().unwrap(), -2i64);
+}
+
+#[test]
+fn write_read_sixteen_bytes() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputByteStream {
+            endianness,
+            ..Default::default()
+        };
+        o.write_u128(0u128);
+        o.write_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        o.write_u128(u128::MAX);
+        o.write_i128(-2i128);
+        o.write_i128(i128::MIN);
+
+        let mut i = InputByteStream::new(o.buffer(), endianness);
+        assert_eq!(i.read_u128().unwrap(), 0);
+        assert_eq!(i.read_u128().unwrap(), 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        assert_eq!(i.read_u128().unwrap(), u128::MAX);
+        assert_eq!(i.read_i128().unwrap(), -2i128);
+        assert_eq!(i.read_i128().unwrap(), i128::MIN);
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
 }
 
 #[test]
@@ -75,17 +107,40 @@ fn write_read_f32() {
     o.write_f32(-1.234);
     o.write_f32(f32::MAX);
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!(1.234, i.read_f32());
-    assert_eq!(-1.234, i.read_f32());
-    assert_eq!(f32::MAX, i.read_f32());
+    assert_eq!(1.234, i.read_f32().unwrap());
+    assert_eq!(-1.234, i.read_f32().unwrap());
+    assert_eq!(f32::MAX, i.read_f32().unwrap());
+}
+
+#[test]
+fn write_read_f64() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputByteStream {
+            endianness,
+            ..Default::default()
+        };
+        o.write_f64(1.234);
+        o.write_f64(-1.234);
+        o.write_f64(f64::MAX);
+        o.write_f64(f64::MIN_POSITIVE);
+
+        let mut i = InputByteStream::new(o.buffer(), endianness);
+        assert_eq!(1.234, i.read_f64().unwrap());
+        assert_eq!(-1.234, i.read_f64().unwrap());
+        assert_eq!(f64::MAX, i.read_f64().unwrap());
+        assert_eq!(f64::MIN_POSITIVE, i.read_f64().unwrap());
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
 }
 
 #[test]
 fn write_read_string() {
     let mut o = OutputByteStream::default();
-    o.write_string(&"hello world!".to_string());
+    o.write_string(&"hello world!".to_string()).unwrap();
     let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
-    assert_eq!("hello world!", i.read_string().as_str());
+    assert_eq!("hello world!", i.read_string().unwrap().as_str());
 }
 
 #[test]
@@ -101,16 +156,16 @@ fn write_read_all() {
         o.write_i32(65536);
         o.write_i64(-5611626018427388000);
         o.write_f32(123.456);
-        o.write_string(&"hello world!".to_string());
+        o.write_string(&"hello world!".to_string()).unwrap();
 
         let mut i = InputByteStream::new(o.buffer(), endianness);
-        assert_eq!(true, i.read_bool());
-        assert_eq!(127, i.read_i8());
-        assert_eq!(30000, i.read_i16());
-        assert_eq!(65536, i.read_i32());
-        assert_eq!(-5611626018427388000, i.read_i64());
-        assert_eq!(123.456, i.read_f32());
-        assert_eq!("hello world!", i.read_string().as_str());
+        assert!(i.read_bool().unwrap());
+        assert_eq!(127, i.read_i8().unwrap());
+        assert_eq!(30000, i.read_i16().unwrap());
+        assert_eq!(65536, i.read_i32().unwrap());
+        assert_eq!(-5611626018427388000, i.read_i64().unwrap());
+        assert_eq!(123.456, i.read_f32().unwrap());
+        assert_eq!("hello world!", i.read_string().unwrap().as_str());
     }
 
     t(Endianness::LittleEndian);
@@ -124,6 +179,440 @@ fn write_in_be_read_in_le() {
     o.write_u16(10000);
 
     let mut i = InputByteStream::new(o.buffer(), Endianness::LittleEndian);
-    assert_eq!(i.read_u8(), 120);
-    assert_ne!(i.read_u16(), 10000);
-}
\ No newline at end of file
+    assert_eq!(i.read_u8().unwrap(), 120);
+    assert_ne!(i.read_u16().unwrap(), 10000);
+}
+
+#[test]
+fn read_past_end() {
+    let mut o = OutputByteStream::default();
+    o.write_u16(10000);
+    o.write_u8(1);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_u16().unwrap(), 10000);
+    assert_eq!(
+        i.read_u32(),
+        Err(StreamError::NotEnoughBytes { requested: 4, offset: 2, remaining: 1 })
+    );
+    // 失败的读取不移动读取位置
+    assert_eq!(i.read_u8().unwrap(), 1);
+    assert!(i.read_bool().is_err());
+}
+
+#[test]
+fn read_truncated_string() {
+    let mut o = OutputByteStream::default();
+    o.write_string(&"hello world!".to_string()).unwrap();
+
+    let mut i = InputByteStream::new(&o.buffer()[..8], Endianness::BigEndian);
+    assert_eq!(
+        i.read_string(),
+        Err(StreamError::NotEnoughBytes { requested: 12, offset: 4, remaining: 4 })
+    );
+}
+
+#[test]
+fn read_invalid_utf8_string() {
+    let mut o = OutputByteStream::default();
+    o.write_u32(4);
+    for byte in [b'a', 0xff, b'b', 0xc3] {
+        o.write_u8(byte);
+    }
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    match i.read_string() {
+        Err(StreamError::InvalidUtf8(err)) => assert_eq!(err.valid_up_to(), 1),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_string_lossy().unwrap(), "a\u{fffd}b\u{fffd}");
+}
+
+#[test]
+fn write_u128_byte_order() {
+    let mut o = OutputByteStream::default();
+    o.write_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+    assert_eq!(o.buffer(), (1..=16).collect::<Vec<u8>>());
+
+    let mut o = OutputByteStream {
+        endianness: Endianness::LittleEndian,
+        ..Default::default()
+    };
+    o.write_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+    assert_eq!(o.buffer(), (1..=16).rev().collect::<Vec<u8>>());
+}
+
+#[test]
+fn write_read_varint() {
+    let mut o = OutputByteStream::default();
+    o.write_var_u32(0);
+    o.write_var_u32(127);
+    o.write_var_u32(300);
+    o.write_var_u32(u32::MAX);
+    o.write_var_u64(u64::MAX);
+    o.write_var_i32(-1);
+    o.write_var_i32(i32::MIN);
+    o.write_var_i64(63);
+    o.write_var_i64(i64::MIN);
+    assert_eq!(&o.buffer()[..4], [0, 127, 0xac, 0x02]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_var_u32().unwrap(), 0);
+    assert_eq!(i.read_var_u32().unwrap(), 127);
+    assert_eq!(i.read_var_u32().unwrap(), 300);
+    assert_eq!(i.read_var_u32().unwrap(), u32::MAX);
+    assert_eq!(i.read_var_u64().unwrap(), u64::MAX);
+    assert_eq!(i.read_var_i32().unwrap(), -1);
+    assert_eq!(i.read_var_i32().unwrap(), i32::MIN);
+    assert_eq!(i.read_var_i64().unwrap(), 63);
+    assert_eq!(i.read_var_i64().unwrap(), i64::MIN);
+}
+
+#[test]
+fn read_invalid_varint() {
+    let overlong = [0x80, 0x00];
+    let mut i = InputByteStream::new(&overlong, Endianness::BigEndian);
+    assert_eq!(i.read_var_u32(), Err(StreamError::VarintOverlong { offset: 0 }));
+    // 失败的读取不移动读取位置
+    assert_eq!(i.read_u8().unwrap(), 0x80);
+
+    let overflow = [0xff, 0xff, 0xff, 0xff, 0x1f];
+    let mut i = InputByteStream::new(&overflow, Endianness::BigEndian);
+    assert_eq!(i.read_var_u32(), Err(StreamError::VarintOverflow { offset: 0 }));
+
+    let too_long = [0xff; 11];
+    let mut i = InputByteStream::new(&too_long, Endianness::BigEndian);
+    assert_eq!(i.read_var_u64(), Err(StreamError::VarintOverflow { offset: 0 }));
+
+    let truncated = [0xff, 0xff];
+    let mut i = InputByteStream::new(&truncated, Endianness::BigEndian);
+    assert!(matches!(i.read_var_u64(), Err(StreamError::NotEnoughBytes { .. })));
+}
+
+#[test]
+fn write_read_string_var() {
+    let mut o = OutputByteStream::default();
+    o.write_string_var("hello world!");
+    assert_eq!(o.buffer().len(), 13);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_string_var().unwrap(), "hello world!");
+}
+
+#[test]
+fn cursor_control() {
+    let mut o = OutputByteStream::default();
+    o.write_u8(1);
+    o.write_u16(0x0203);
+    o.write_u32(0x0405_0607);
+    o.write_u8(8);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.position(), 0);
+    assert_eq!(i.remaining(), 8);
+    assert_eq!(i.peek_u8().unwrap(), 1);
+    assert_eq!(i.peek_u16().unwrap(), 0x0102);
+    assert_eq!(i.peek_u32().unwrap(), 0x0102_0304);
+    assert_eq!(i.position(), 0);
+
+    i.skip(1).unwrap();
+    assert_eq!(i.read_u16().unwrap(), 0x0203);
+    assert_eq!(i.seek(SeekFrom::End(-1)).unwrap(), 7);
+    assert_eq!(i.read_u8().unwrap(), 8);
+    assert!(i.is_empty());
+    assert!(i.peek_u8().is_err());
+
+    assert_eq!(i.seek(SeekFrom::Current(-5)).unwrap(), 3);
+    assert_eq!(i.read_u32().unwrap(), 0x0405_0607);
+    assert_eq!(i.seek(SeekFrom::Start(1)).unwrap(), 1);
+    assert_eq!(i.position(), 1);
+
+    assert_eq!(
+        i.seek(SeekFrom::Current(-2)),
+        Err(StreamError::SeekOutOfBounds { position: -1, len: 8 })
+    );
+    assert_eq!(
+        i.seek(SeekFrom::Start(9)),
+        Err(StreamError::SeekOutOfBounds { position: 9, len: 8 })
+    );
+    assert_eq!(
+        i.skip(8),
+        Err(StreamError::NotEnoughBytes { requested: 8, offset: 1, remaining: 7 })
+    );
+    assert_eq!(i.position(), 1);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::LittleEndian);
+    assert_eq!(i.peek_u16().unwrap(), 0x0201);
+}
+
+#[test]
+fn reserve_and_patch() {
+    fn t(endianness: Endianness) {
+        let mut o = OutputByteStream {
+            endianness,
+            ..Default::default()
+        };
+        o.write_u8(0xaa);
+        let len = o.reserve_u32();
+        let kind = o.reserve_u16();
+        let start = o.buffer().len();
+        o.write_string(&"payload".to_string()).unwrap();
+        o.write_u64(7);
+        let checksum = o.reserve_u8();
+        o.write_u8(0xbb);
+
+        o.patch_u32(len, (o.buffer().len() - start) as u32);
+        o.patch_u16(kind, 0x0102);
+        o.patch_u8(checksum, 0xcc);
+        assert_eq!(len.offset(), 1);
+
+        let mut i = InputByteStream::new(o.buffer(), endianness);
+        assert_eq!(i.read_u8().unwrap(), 0xaa);
+        assert_eq!(i.read_u32().unwrap(), 11 + 8 + 2);
+        assert_eq!(i.read_u16().unwrap(), 0x0102);
+        assert_eq!(i.read_string().unwrap(), "payload");
+        assert_eq!(i.read_u64().unwrap(), 7);
+        assert_eq!(i.read_u8().unwrap(), 0xcc);
+        assert_eq!(i.read_u8().unwrap(), 0xbb);
+        assert!(i.is_empty());
+    }
+
+    t(Endianness::LittleEndian);
+    t(Endianness::BigEndian);
+}
+
+#[test]
+fn write_read_sections() {
+    let mut o = OutputByteStream::default();
+    o.write_u8(1);
+    let outer = o.begin_section();
+    o.write_u16(2);
+    let inner = o.begin_section();
+    o.write_string(&"new field".to_string()).unwrap();
+    o.end_section(inner);
+    o.write_u32(3);
+    o.end_section(outer);
+    let unknown = o.begin_section();
+    o.write_u64(4);
+    o.end_section(unknown);
+    o.write_u8(5);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_u8().unwrap(), 1);
+    let mut section = i.enter_section().unwrap();
+    assert_eq!(section.remaining(), 2 + 4 + 13 + 4);
+    assert_eq!(section.read_u16().unwrap(), 2);
+    // 旧版本不认识的内层段直接跳过
+    section.skip_section().unwrap();
+    assert_eq!(section.read_u32().unwrap(), 3);
+    assert!(section.read_u8().is_err());
+    i.skip_section().unwrap();
+    assert_eq!(i.read_u8().unwrap(), 5);
+    assert!(i.is_empty());
+}
+
+#[test]
+fn enter_truncated_section() {
+    let mut o = OutputByteStream::default();
+    let section = o.begin_section();
+    o.write_u64(1);
+    o.end_section(section);
+
+    let mut i = InputByteStream::new(&o.buffer()[..10], Endianness::BigEndian);
+    assert_eq!(
+        i.enter_section().err(),
+        Some(StreamError::NotEnoughBytes { requested: 8, offset: 4, remaining: 6 })
+    );
+    assert_eq!(i.position(), 0);
+}
+
+#[test]
+fn take_sub_stream() {
+    let mut o = OutputByteStream::default();
+    o.write_u16(3);
+    o.write_u8(1);
+    o.write_u16(2);
+    o.write_u32(0xdead_beef);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    let len = i.read_u16().unwrap() as usize;
+    let mut payload = i.take(len).unwrap();
+    assert_eq!(i.position(), 5);
+    assert_eq!(payload.read_u8().unwrap(), 1);
+    assert_eq!(payload.read_u16().unwrap(), 2);
+    assert_eq!(
+        payload.read_u8(),
+        Err(StreamError::NotEnoughBytes { requested: 1, offset: 3, remaining: 0 })
+    );
+    assert!(payload.seek(SeekFrom::Start(4)).is_err());
+    assert_eq!(i.read_u32().unwrap(), 0xdead_beef);
+
+    assert!(i.take(1).is_err());
+    assert_eq!(i.take(0).unwrap().remaining(), 0);
+}
+
+#[test]
+fn fixed_output_stream() {
+    let mut buf = [0u8; 16];
+    let mut o = FixedOutputByteStream::new(&mut buf, Endianness::LittleEndian);
+    o.write_u16(0x0102).unwrap();
+    o.write_var_u32(300).unwrap();
+    o.write_string("abc").unwrap();
+    assert_eq!(o.buffer(), [2, 1, 0xac, 0x02, 3, 0, 0, 0, b'a', b'b', b'c']);
+    assert_eq!(o.remaining(), 5);
+
+    assert_eq!(
+        o.write_u64(1),
+        Err(StreamError::NotEnoughSpace { requested: 8, offset: 11, remaining: 5 })
+    );
+    // 长度前缀放得下但内容放不下时什么都不写
+    assert!(o.write_string("ab").is_err());
+    assert_eq!(o.remaining(), 5);
+    o.write_u32(0xdead_beef).unwrap();
+    o.write_u8(7).unwrap();
+    assert!(o.write_u8(0).is_err());
+
+    let written = o.finish();
+    let mut i = InputByteStream::new(written, Endianness::LittleEndian);
+    assert_eq!(i.read_u16().unwrap(), 0x0102);
+    assert_eq!(i.read_var_u32().unwrap(), 300);
+    assert_eq!(i.read_string().unwrap(), "abc");
+    assert_eq!(i.read_u32().unwrap(), 0xdead_beef);
+    assert_eq!(i.read_u8().unwrap(), 7);
+    assert!(i.is_empty());
+}
+
+#[test]
+fn fixed_reserve_patch_and_sections() {
+    let mut buf = [0u8; 16];
+    let mut o = FixedOutputByteStream::new(&mut buf, Endianness::LittleEndian);
+    let kind = o.reserve_u16().unwrap();
+    let outer = o.begin_section().unwrap();
+    o.write_u8(1).unwrap();
+    let inner = o.begin_section().unwrap();
+    o.write_u16(2).unwrap();
+    o.end_section(inner);
+    o.end_section(outer);
+    o.patch_u16(kind, 0x0102);
+    assert_eq!(o.remaining(), 3);
+    assert!(o.reserve_u32().is_err());
+    assert!(o.begin_section().is_err());
+    assert_eq!(o.remaining(), 3);
+
+    let written = o.finish();
+    let mut i = InputByteStream::new(written, Endianness::LittleEndian);
+    assert_eq!(i.read_u16().unwrap(), 0x0102);
+    let mut section = i.enter_section().unwrap();
+    assert_eq!(section.read_u8().unwrap(), 1);
+    let mut inner = section.enter_section().unwrap();
+    assert_eq!(inner.read_u16().unwrap(), 2);
+    assert!(i.is_empty());
+}
+
+#[test]
+fn write_string_accepts_str() {
+    let owned = String::from("owned");
+    let mut o = OutputByteStream::default();
+    o.write_string("literal").unwrap();
+    o.write_string(&owned).unwrap();
+    o.write_string(&owned[1..3]).unwrap();
+    o.write_string_var("var");
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_string().unwrap(), "literal");
+    assert_eq!(i.read_string().unwrap(), "owned");
+    assert_eq!(i.read_string().unwrap(), "wn");
+    assert_eq!(i.read_string_var().unwrap(), "var");
+    assert!(i.is_empty());
+}
+
+#[test]
+fn reuse_buffer() {
+    let mut o = OutputByteStream::with_capacity(16, Endianness::LittleEndian);
+    assert!(o.capacity() >= 16);
+    o.write_u32(1);
+    o.write_u16(2);
+    assert_eq!(o.buffer(), [1, 0, 0, 0, 2, 0]);
+
+    o.clear();
+    assert!(o.buffer().is_empty());
+    o.write_u8(9);
+    assert_eq!(o.buffer(), [9]);
+
+    o.reserve(100);
+    let capacity = o.capacity();
+    assert!(capacity >= 101);
+    let ptr_after_reserve = o.buf.as_ptr();
+    for n in 0..100 {
+        o.write_u8(n);
+    }
+    assert_eq!(o.capacity(), capacity);
+    assert_eq!(o.buf.as_ptr(), ptr_after_reserve);
+
+    let v = o.into_vec();
+    assert_eq!(v.len(), 101);
+    assert_eq!(v[0], 9);
+
+    // from_vec 接着已有数据写入
+    let mut o = OutputByteStream::from_vec(v, Endianness::BigEndian);
+    o.write_u16(0x0102);
+    assert_eq!(o.buffer().len(), 103);
+    assert_eq!(&o.buffer()[101..], [1, 2]);
+    o.clear();
+    o.write_u8(7);
+    assert_eq!(o.into_vec(), [7]);
+}
+
+#[test]
+fn borrowed_reads() {
+    let mut o = OutputByteStream::default();
+    o.write_string(&"chat message".to_string()).unwrap();
+    o.write_u8(0xff);
+    o.write_u8(0xfe);
+    o.write_u32(2);
+    o.write_u8(0xc3);
+    o.write_u8(0x28);
+
+    let data = o.buffer();
+    let mut i = InputByteStream::new(data, Endianness::BigEndian);
+    let message = i.read_str().unwrap();
+    assert_eq!(message, "chat message");
+    assert_eq!(message.as_ptr(), data[4..].as_ptr());
+
+    let bytes = i.read_byte_slice(2).unwrap();
+    assert_eq!(bytes, [0xff, 0xfe]);
+    assert_eq!(bytes.as_ptr(), data[16..].as_ptr());
+
+    // 非法 UTF-8 时不移动读取位置
+    assert!(matches!(i.read_str(), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!(i.position(), 18);
+    assert!(i.read_byte_slice(7).is_err());
+    assert_eq!(i.read_byte_slice(6).unwrap(), [0, 0, 0, 2, 0xc3, 0x28]);
+}
+
+#[test]
+fn raw_bytes() {
+    let blob: Vec<u8> = (0..=255).collect();
+    let mut o = OutputByteStream::with_capacity(0, Endianness::BigEndian);
+    o.write_u8(1);
+    o.write_raw(&blob);
+    o.write_raw(&[]);
+    assert_eq!(o.buffer().len(), 257);
+    assert_eq!(&o.buffer()[1..], blob.as_slice());
+
+    let mut buf = [0u8; 8];
+    let mut fixed = FixedOutputByteStream::new(&mut buf, Endianness::BigEndian);
+    fixed.write_raw(&blob[..6]).unwrap();
+    assert!(fixed.write_raw(&blob[..3]).is_err());
+    assert_eq!(fixed.buffer(), &blob[..6]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    let mut out = [0u8; 256];
+    assert!(i.read_raw(&mut [0u8; 258]).is_err());
+    assert_eq!(i.read_u8().unwrap(), 1);
+    i.read_raw(&mut out).unwrap();
+    assert_eq!(out.as_slice(), blob.as_slice());
+    assert!(i.is_empty());
+}
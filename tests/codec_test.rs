@@ -0,0 +1,109 @@
+use memory_stream::bit_stream::input_bit_stream::InputBitStream;
+use memory_stream::bit_stream::output_bit_stream::OutputBitStream;
+use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::input_byte_stream::InputByteStream;
+use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::codec::{Decode, Encode};
+use memory_stream::error::Result;
+use memory_stream::stream::{StreamReader, StreamWriter};
+
+#[derive(Debug, PartialEq)]
+struct Player {
+    id: u32,
+    name: String,
+    scores: Vec<i16>,
+    guild: Option<String>,
+    position: (f32, f32, f32),
+    color: [u8; 3],
+    pet: Option<Box<Player>>,
+}
+
+impl Encode for Player {
+    fn encode<W: StreamWriter + ?Sized>(&self, w: &mut W) -> Result<()> {
+        self.id.encode(w)?;
+        self.name.encode(w)?;
+        self.scores.encode(w)?;
+        self.guild.encode(w)?;
+        self.position.encode(w)?;
+        self.color.encode(w)?;
+        self.pet.encode(w)
+    }
+}
+
+impl Decode for Player {
+    fn decode<R: StreamReader + ?Sized>(r: &mut R) -> Result<Self> {
+        Ok(Player {
+            id: Decode::decode(r)?,
+            name: Decode::decode(r)?,
+            scores: Decode::decode(r)?,
+            guild: Decode::decode(r)?,
+            position: Decode::decode(r)?,
+            color: Decode::decode(r)?,
+            pet: Decode::decode(r)?,
+        })
+    }
+}
+
+fn sample() -> Player {
+    Player {
+        id: 7,
+        name: "alice".to_string(),
+        scores: vec![10, -20, 30],
+        guild: None,
+        position: (1.0, 2.5, -3.0),
+        color: [255, 128, 0],
+        pet: Some(Box::new(Player {
+            id: 8,
+            name: "cat".to_string(),
+            scores: vec![],
+            guild: Some("home".to_string()),
+            position: (0.0, 0.0, 0.0),
+            color: [0, 0, 0],
+            pet: None,
+        })),
+    }
+}
+
+#[test]
+fn encode_decode_byte_stream() {
+    let mut o = OutputByteStream::default();
+    sample().encode(&mut o).unwrap();
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Player::decode(&mut i).unwrap(), sample());
+}
+
+#[test]
+fn encode_decode_bit_stream() {
+    let mut o = OutputBitStream::default();
+    sample().encode(&mut o).unwrap();
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Player::decode(&mut i).unwrap(), sample());
+}
+
+#[test]
+fn encode_layout() {
+    let mut o = OutputByteStream::default();
+    (1u8, vec![2u16, 3], Some(4u8), None::<u8>, [5u8, 6]).encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [1, 0, 0, 0, 2, 0, 2, 0, 3, 1, 4, 0, 5, 6]);
+}
+
+#[test]
+fn decode_truncated() {
+    let mut o = OutputByteStream::default();
+    vec![1u32, 2, 3].encode(&mut o).unwrap();
+
+    let mut i = InputByteStream::new(&o.buffer()[..10], Endianness::BigEndian);
+    assert!(Vec::<u32>::decode(&mut i).is_err());
+}
+
+#[test]
+fn char_codec() {
+    let mut o = OutputByteStream::default();
+    ('x', '€').encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), "x€".as_bytes());
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(<(char, char)>::decode(&mut i).unwrap(), ('x', '€'));
+}
@@ -0,0 +1,336 @@
+#![allow(clippy::unnecessary_to_owned)]
+
+use memory_stream::bit_stream::fixed_output_bit_stream::FixedOutputBitStream;
+use memory_stream::bit_stream::input_bit_stream::InputBitStream;
+use memory_stream::bit_stream::output_bit_stream::OutputBitStream;
+use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::fixed_output_byte_stream::FixedOutputByteStream;
+use memory_stream::byte_stream::input_byte_stream::InputByteStream;
+use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::codec::{Decode, Encode};
+use memory_stream::config::{LengthPrefix, StreamConfig};
+use memory_stream::error::StreamError;
+use memory_stream::stream::{StreamReader, StreamWriter, StringPadding};
+
+#[test]
+fn config_defaults() {
+    let config = StreamConfig::new();
+    assert_eq!(config.get_endianness(), Endianness::BigEndian);
+    assert_eq!(config.get_capacity(), 1024);
+    assert_eq!(config.get_max_size(), None);
+    assert_eq!(config.get_length_prefix(), LengthPrefix::U32);
+    assert!(!config.is_strict_bool());
+    assert!(!config.is_strict_padding());
+}
+
+#[test]
+fn length_prefix_on_byte_streams() {
+    fn t(length_prefix: LengthPrefix, expected: &[u8]) {
+        let config = StreamConfig::new()
+            .endianness(Endianness::LittleEndian)
+            .length_prefix(length_prefix);
+        let mut o = OutputByteStream::with_config(&config);
+        o.write_string(&"ab".to_string()).unwrap();
+        vec![7u8].encode(&mut o).unwrap();
+        assert_eq!(o.buffer(), expected);
+
+        let mut buf = [0u8; 16];
+        let mut fixed = FixedOutputByteStream::with_config(&mut buf, &config);
+        fixed.write_string("ab").unwrap();
+        vec![7u8].encode(&mut fixed).unwrap();
+        assert_eq!(fixed.buffer(), expected);
+
+        let mut i = InputByteStream::with_config(expected, &config);
+        assert_eq!(i.read_string().unwrap(), "ab");
+        assert_eq!(Vec::<u8>::decode(&mut i).unwrap(), [7]);
+        assert!(i.is_empty());
+    }
+
+    t(LengthPrefix::U8, &[2, b'a', b'b', 1, 7]);
+    t(LengthPrefix::U16, &[2, 0, b'a', b'b', 1, 0, 7]);
+    t(LengthPrefix::U32, &[2, 0, 0, 0, b'a', b'b', 1, 0, 0, 0, 7]);
+    t(LengthPrefix::VarInt, &[2, b'a', b'b', 1, 7]);
+}
+
+#[test]
+fn length_prefix_on_bit_streams() {
+    let config = StreamConfig::new().length_prefix(LengthPrefix::U16);
+    let mut o = OutputBitStream::with_config(&config);
+    o.write_bool(true);
+    o.write_string(&"xyz".to_string()).unwrap();
+
+    let mut buf = [0u8; 8];
+    let mut fixed = FixedOutputBitStream::with_config(&mut buf, &config);
+    fixed.write_bool(true).unwrap();
+    fixed.write_string("xyz").unwrap();
+    assert_eq!(fixed.buffer(), o.buffer());
+
+    let mut i = InputBitStream::with_config(o.buffer(), &config);
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_string().unwrap(), "xyz");
+}
+
+#[test]
+fn length_too_large_for_prefix() {
+    let config = StreamConfig::new().length_prefix(LengthPrefix::U8);
+    let long = "a".repeat(256);
+
+    let mut o = OutputByteStream::with_config(&config);
+    assert_eq!(
+        StreamWriter::write_string(&mut o, &long),
+        Err(StreamError::LengthTooLarge { len: 256, max: 255 })
+    );
+    assert!(o.buffer().is_empty());
+    // 固有方法同样返回错误而不是 panic
+    assert_eq!(o.write_string(&long), Err(StreamError::LengthTooLarge { len: 256, max: 255 }));
+    assert!(o.write_len(300).is_err());
+    assert!(o.buffer().is_empty());
+
+    let mut o = OutputBitStream::with_config(&config);
+    o.write_bool(true);
+    assert_eq!(o.write_string(&long), Err(StreamError::LengthTooLarge { len: 256, max: 255 }));
+    assert_eq!(o.bit_len(), 1);
+
+    let mut buf = [0u8; 512];
+    let mut fixed = FixedOutputBitStream::with_config(&mut buf, &config);
+    assert!(fixed.write_string(&long).is_err());
+    assert_eq!(fixed.bit_len(), 0);
+}
+
+#[test]
+fn max_size_on_fixed_streams() {
+    let mut buf = [0u8; 8];
+    let mut o = FixedOutputByteStream::with_config(&mut buf, &StreamConfig::new().max_size(3));
+    assert_eq!(o.capacity(), 3);
+    assert!(o.write_u32(1).is_err());
+}
+
+#[test]
+fn strict_bool() {
+    let data = [0, 1, 2];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert!(!i.read_bool().unwrap());
+    assert!(i.read_bool().unwrap());
+    assert!(i.read_bool().unwrap());
+
+    let mut i = InputByteStream::with_config(&data, &StreamConfig::new().strict_bool(true));
+    assert!(!i.read_bool().unwrap());
+    assert!(i.read_bool().unwrap());
+    assert_eq!(i.read_bool(), Err(StreamError::InvalidBool { value: 2, offset: 2 }));
+    assert_eq!(i.position(), 2);
+}
+
+#[test]
+fn strict_padding() {
+    let data = [0b0000_0101, 0xff];
+    let mut i = InputBitStream::new(&data);
+    i.read_bits(1).unwrap();
+    i.align_to_byte().unwrap();
+    assert_eq!(i.read_u8().unwrap(), 0xff);
+
+    let mut i = InputBitStream::with_config(&data, &StreamConfig::new().strict_padding(true));
+    i.read_bits(1).unwrap();
+    assert_eq!(i.align_to_byte(), Err(StreamError::NonZeroPadding { bit_offset: 1 }));
+    assert_eq!(i.bit_position(), 1);
+    i.read_bits(3).unwrap();
+    i.align_to_byte().unwrap();
+    assert_eq!(i.bit_position(), 8);
+}
+
+#[test]
+fn sub_streams_keep_config() {
+    let data = [2, 1, b'a', 9];
+    let config = StreamConfig::new().length_prefix(LengthPrefix::U8).strict_bool(true);
+    let mut i = InputByteStream::with_config(&data, &config);
+    let len = i.read_u8().unwrap() as usize;
+    let mut payload = i.take(len).unwrap();
+    assert_eq!(payload.read_string().unwrap(), "a");
+    assert_eq!(i.read_bool(), Err(StreamError::InvalidBool { value: 9, offset: 3 }));
+}
+
+#[test]
+fn hostile_lengths_fail_before_allocation() {
+    let data = [0xff, 0xff, 0xff, 0xff, b'a'];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert_eq!(
+        i.read_string(),
+        Err(StreamError::NotEnoughBytes { requested: u32::MAX as usize, offset: 4, remaining: 1 })
+    );
+    assert_eq!(i.allocated(), 0);
+
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert!(matches!(Vec::<u64>::decode(&mut i), Err(StreamError::NotEnoughBytes { .. })));
+
+    // 每个 u64 至少占 8 个字节
+    let short = [0, 0, 0, 2, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let mut i = InputByteStream::new(&short, Endianness::BigEndian);
+    assert_eq!(
+        Vec::<u64>::decode(&mut i),
+        Err(StreamError::NotEnoughBytes { requested: 16, offset: 4, remaining: 9 })
+    );
+    assert_eq!(i.allocated(), 0);
+
+    let mut i = InputBitStream::new(&data);
+    assert!(matches!(i.read_string(), Err(StreamError::NotEnoughBits { .. })));
+}
+
+#[test]
+fn string_and_collection_limits() {
+    let mut o = OutputByteStream::default();
+    o.write_string(&"hello".to_string()).unwrap();
+    vec![1u16, 2, 3].encode(&mut o).unwrap();
+
+    let config = StreamConfig::new().max_string_len(4);
+    let mut i = InputByteStream::with_config(o.buffer(), &config);
+    assert_eq!(i.read_string(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+
+    let config = StreamConfig::new().max_collection_len(2);
+    let mut i = InputByteStream::with_config(o.buffer(), &config);
+    assert_eq!(i.read_string().unwrap(), "hello");
+    assert_eq!(Vec::<u16>::decode(&mut i), Err(StreamError::CollectionTooLong { len: 3, max: 2 }));
+
+    let mut o = OutputBitStream::default();
+    o.write_string(&"hello".to_string()).unwrap();
+    let mut i = InputBitStream::with_config(o.buffer(), &StreamConfig::new().max_string_len(4));
+    assert_eq!(i.read_string(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+}
+
+#[test]
+fn rejected_strings_keep_position() {
+    let mut o = OutputByteStream::default();
+    o.write_string("hello").unwrap();
+    o.write_string_var("world");
+    let config = StreamConfig::new().max_string_len(4);
+
+    let mut i = InputByteStream::with_config(o.buffer(), &config);
+    assert_eq!(i.read_str(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(i.read_string(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(i.read_string_lossy(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(i.position(), 0);
+    i.seek(std::io::SeekFrom::Start(9)).unwrap();
+    assert_eq!(
+        StreamReader::read_string_var(&mut i),
+        Err(StreamError::StringTooLong { len: 5, max: 4 })
+    );
+    assert_eq!(i.position(), 9);
+
+    let mut i = InputByteStream::new(&o.buffer()[..8], Endianness::BigEndian);
+    assert!(StreamReader::read_string(&mut i).is_err());
+    assert_eq!(i.position(), 0);
+
+    let mut o = OutputBitStream::default();
+    o.write_string("hello").unwrap();
+    o.write_string_var("world");
+    let mut i = InputBitStream::with_config(o.buffer(), &config);
+    assert_eq!(i.read_str(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(
+        StreamReader::read_string(&mut i),
+        Err(StreamError::StringTooLong { len: 5, max: 4 })
+    );
+    assert_eq!(i.read_string_lossy(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(i.bit_position(), 0);
+    i.skip_bits(9 * 8).unwrap();
+    assert_eq!(i.read_string_var(), Err(StreamError::StringTooLong { len: 5, max: 4 }));
+    assert_eq!(i.bit_position(), 9 * 8);
+
+    let mut i = InputBitStream::new(&o.buffer()[..8]);
+    assert!(i.read_string().is_err());
+    assert_eq!(i.bit_position(), 0);
+}
+
+#[test]
+fn rejected_trait_strings_keep_position() {
+    let data = [3, b'a', b'b', b'c', 0, 3, 0xff, b'x', 0];
+    let config = StreamConfig::new().max_string_len(2);
+
+    let mut i = InputByteStream::with_config(&data, &config);
+    assert_eq!(i.read_string_u8(), Err(StreamError::StringTooLong { len: 3, max: 2 }));
+    assert!(i.read_string_u16().is_err());
+    assert_eq!(
+        i.read_string_fixed(3, StringPadding::Zero),
+        Err(StreamError::StringTooLong { len: 3, max: 2 })
+    );
+    assert_eq!(i.position(), 0);
+    i.seek(std::io::SeekFrom::Start(6)).unwrap();
+    assert!(matches!(i.read_string_nul(), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!(i.position(), 6);
+
+    let mut i = InputBitStream::with_config(&data, &config);
+    assert_eq!(i.read_string_u8(), Err(StreamError::StringTooLong { len: 3, max: 2 }));
+    assert_eq!(
+        i.read_string_fixed(3, StringPadding::Space),
+        Err(StreamError::StringTooLong { len: 3, max: 2 })
+    );
+    assert_eq!(i.bit_position(), 0);
+    i.skip_bits(6 * 8).unwrap();
+    assert!(matches!(i.read_string_nul(), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!(i.bit_position(), 6 * 8);
+}
+
+#[test]
+fn allocation_limit() {
+    let mut o = OutputByteStream::default();
+    o.write_string(&"abc".to_string()).unwrap();
+    vec![1u32, 2].encode(&mut o).unwrap();
+    o.write_string(&"de".to_string()).unwrap();
+
+    let config = StreamConfig::new().max_alloc(12);
+    let mut i = InputByteStream::with_config(o.buffer(), &config);
+    assert_eq!(i.read_string().unwrap(), "abc");
+    assert_eq!(Vec::<u32>::decode(&mut i).unwrap(), [1, 2]);
+    assert_eq!(i.allocated(), 11);
+    assert_eq!(
+        i.read_string(),
+        Err(StreamError::AllocationLimit { requested: 2, remaining: 1 })
+    );
+
+    i.reset_allocated();
+    i.seek(std::io::SeekFrom::Start(19)).unwrap();
+    assert_eq!(i.read_string().unwrap(), "de");
+}
+
+#[test]
+fn rejected_reads_refund_allocation() {
+    let data = [0, 0, 0, 3, b'a', 0xff, b'b'];
+    let config = StreamConfig::new().max_alloc(4);
+    let mut i = InputByteStream::with_config(&data, &config);
+    assert!(matches!(i.read_string(), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!((i.position(), i.allocated()), (0, 0));
+    assert_eq!(i.read_string_lossy().unwrap(), "a\u{fffd}b");
+    assert_eq!(i.allocated(), 3);
+
+    let mut i = InputBitStream::with_config(&data, &config);
+    assert!(i.read_string().is_err());
+    assert!(i.read_string().is_err());
+    assert_eq!((i.bit_position(), i.allocated()), (0, 0));
+    assert_eq!(i.read_string_lossy().unwrap(), "a\u{fffd}b");
+    assert_eq!(i.allocated(), 3);
+}
+
+#[test]
+fn allocation_limit_shared_with_sections() {
+    let mut o = OutputByteStream::default();
+    for _ in 0..3 {
+        let section = o.begin_section();
+        o.write_string("0123456789").unwrap();
+        o.end_section(section);
+    }
+
+    let mut i = InputByteStream::with_config(o.buffer(), &StreamConfig::new().max_alloc(15));
+    assert_eq!(i.enter_section().unwrap().read_string().unwrap(), "0123456789");
+    assert_eq!(i.allocated(), 10);
+    assert_eq!(
+        i.enter_section().unwrap().read_string(),
+        Err(StreamError::AllocationLimit { requested: 10, remaining: 5 })
+    );
+    assert!(i.enter_section().unwrap().read_string().is_err());
+    assert_eq!(i.allocated(), 10);
+
+    let mut o = OutputBitStream::default();
+    o.write_string("0123456789").unwrap();
+    o.write_string("0123456789").unwrap();
+    let mut i = InputBitStream::with_config(o.buffer(), &StreamConfig::new().max_alloc(15));
+    assert_eq!(i.take(112).unwrap().read_string().unwrap(), "0123456789");
+    assert_eq!(i.allocated(), 10);
+    assert!(matches!(i.read_string(), Err(StreamError::AllocationLimit { .. })));
+}
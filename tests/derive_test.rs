@@ -0,0 +1,241 @@
+use memory_stream::bit_stream::input_bit_stream::InputBitStream;
+use memory_stream::bit_stream::output_bit_stream::OutputBitStream;
+use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::input_byte_stream::InputByteStream;
+use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::codec::{Decode, Encode, StreamDecode, StreamEncode};
+use memory_stream::error::StreamError;
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Header {
+    #[bits(3)]
+    version: u8,
+    #[bits(5)]
+    flags: u8,
+    #[bits(11)]
+    length: u16,
+    #[bits(17)]
+    delta: i32,
+    #[bits(1)]
+    compressed: bool,
+}
+
+#[derive(Debug, Default, PartialEq, StreamEncode, StreamDecode)]
+struct Cache(u32);
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Message {
+    header: Header,
+    #[endianness(little)]
+    sequence: u32,
+    body: Vec<Command>,
+    #[skip]
+    cache: Cache,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[bits(2)]
+enum Command {
+    Stop,
+    Move { x: f32, y: f32 },
+    Say(String),
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[endianness(little)]
+struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+fn sample() -> Message {
+    Message {
+        header: Header {
+            version: 5,
+            flags: 17,
+            length: 2000,
+            delta: -65536,
+            compressed: true,
+        },
+        sequence: 0x0102_0304,
+        body: vec![
+            Command::Move { x: 1.0, y: -2.0 },
+            Command::Say("hi".to_string()),
+            Command::Stop,
+        ],
+        cache: Cache(42),
+    }
+}
+
+fn decoded_sample() -> Message {
+    Message {
+        cache: Cache::default(),
+        ..sample()
+    }
+}
+
+#[test]
+fn derive_bit_stream() {
+    let mut o = OutputBitStream::default();
+    sample().encode(&mut o).unwrap();
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Message::decode(&mut i).unwrap(), decoded_sample());
+}
+
+#[test]
+fn derive_byte_stream() {
+    let mut o = OutputByteStream::default();
+    sample().encode(&mut o).unwrap();
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Message::decode(&mut i).unwrap(), decoded_sample());
+}
+
+#[test]
+fn derive_bits_are_packed() {
+    let mut o = OutputBitStream::default();
+    sample().header.encode(&mut o).unwrap();
+    assert_eq!(o.buffer().len(), 5);
+}
+
+#[test]
+fn derive_endianness() {
+    let mut o = OutputByteStream::default();
+    Pair { first: 1u16, second: 2u16 }.encode(&mut o).unwrap();
+    0x0304u16.encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [1, 0, 2, 0, 3, 4]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Pair::<u16>::decode(&mut i).unwrap(), Pair { first: 1, second: 2 });
+    assert_eq!(u16::decode(&mut i).unwrap(), 0x0304);
+}
+
+#[test]
+fn derive_unknown_variant() {
+    let mut o = OutputBitStream::default();
+    o.write_bits(3, 2);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(
+        Command::decode(&mut i),
+        Err(StreamError::UnknownVariant { type_name: "Command", tag: 3 })
+    );
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+struct Color {
+    r: u8,
+    g: u8,
+    b: u8,
+    w: u8,
+}
+
+#[test]
+fn derive_field_names_do_not_clash() {
+    let color = Color { r: 1, g: 2, b: 3, w: 4 };
+    let mut o = OutputByteStream::default();
+    color.encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [1, 2, 3, 4]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::LittleEndian);
+    assert_eq!(Color::decode(&mut i).unwrap(), color);
+}
+
+// 两个变体正好占满 1 位标签
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[bits(1)]
+enum Switch {
+    Off,
+    On,
+}
+
+#[test]
+fn derive_tag_bits_filled() {
+    let mut o = OutputBitStream::default();
+    Switch::On.encode(&mut o).unwrap();
+    Switch::Off.encode(&mut o).unwrap();
+    assert_eq!(o.bit_len(), 2);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Switch::decode(&mut i).unwrap(), Switch::On);
+    assert_eq!(Switch::decode(&mut i).unwrap(), Switch::Off);
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+enum Op {
+    Ping = 7,
+    Pong = 9,
+    Next,
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+#[bits(3)]
+enum Level {
+    Low = 2,
+    High = 7,
+}
+
+#[test]
+fn derive_explicit_discriminants() {
+    let mut o = OutputByteStream::default();
+    Op::Pong.encode(&mut o).unwrap();
+    Op::Next.encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [0, 0, 0, 9, 0, 0, 0, 10]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Op::decode(&mut i).unwrap(), Op::Pong);
+    assert_eq!(Op::decode(&mut i).unwrap(), Op::Next);
+
+    let mut i = InputByteStream::new(&[0, 0, 0, 1], Endianness::BigEndian);
+    assert_eq!(
+        Op::decode(&mut i),
+        Err(StreamError::UnknownVariant { type_name: "Op", tag: 1 })
+    );
+
+    let mut o = OutputBitStream::default();
+    Level::High.encode(&mut o).unwrap();
+    Level::Low.encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [0b010_111]);
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Level::decode(&mut i).unwrap(), Level::High);
+    assert_eq!(Level::decode(&mut i).unwrap(), Level::Low);
+}
+
+#[derive(Debug, PartialEq, StreamEncode, StreamDecode)]
+enum Never {}
+
+#[test]
+fn derive_empty_enum() {
+    let mut i = InputByteStream::new(&[0, 0, 0, 0], Endianness::BigEndian);
+    assert_eq!(
+        Never::decode(&mut i),
+        Err(StreamError::UnknownVariant { type_name: "Never", tag: 0 })
+    );
+}
+
+#[derive(Debug, Default, PartialEq, StreamEncode, StreamDecode)]
+struct Marker {
+    #[skip]
+    cached: u64,
+}
+
+#[test]
+fn derive_min_bits() {
+    assert_eq!(<Marker as Decode>::MIN_BITS, 0);
+    assert_eq!(<Command as Decode>::MIN_BITS, 2);
+
+    let markers = vec![Marker::default(), Marker::default(), Marker::default()];
+    let mut o = OutputByteStream::default();
+    markers.encode(&mut o).unwrap();
+    assert_eq!(o.buffer(), [0, 0, 0, 3]);
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Vec::<Marker>::decode(&mut i).unwrap(), markers);
+
+    let mut o = OutputBitStream::default();
+    markers.encode(&mut o).unwrap();
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Vec::<Marker>::decode(&mut i).unwrap(), markers);
+}
@@ -0,0 +1,99 @@
+use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::input_byte_stream::InputByteStream;
+use memory_stream::byte_stream::java_data_stream::{JavaDataInput, JavaDataOutput};
+use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::error::StreamError;
+
+// DataOutputStream 写出的字节
+#[test]
+fn primitives_match_data_output_stream() {
+    // 流的字节序不影响 Java 兼容接口
+    let mut o = OutputByteStream {
+        endianness: Endianness::LittleEndian,
+        ..Default::default()
+    };
+    let mut java = JavaDataOutput::new(&mut o);
+    java.write_boolean(true).unwrap();
+    java.write_byte(-2).unwrap();
+    java.write_short(-2).unwrap();
+    java.write_char('A' as u16).unwrap();
+    java.write_int(0x0102_0304).unwrap();
+    java.write_long(-1).unwrap();
+    java.write_float(1.5).unwrap();
+    java.write_double(1.0).unwrap();
+    java.write_double(-0.1).unwrap();
+    java.write_chars("hi").unwrap();
+    assert_eq!(
+        o.buffer(),
+        [
+            0x01,
+            0xfe,
+            0xff, 0xfe,
+            0x00, 0x41,
+            0x01, 0x02, 0x03, 0x04,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0x3f, 0xc0, 0x00, 0x00,
+            0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xbf, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a,
+            0x00, 0x68, 0x00, 0x69,
+        ]
+    );
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::LittleEndian);
+    let mut java = JavaDataInput::new(&mut i);
+    assert!(java.read_boolean().unwrap());
+    assert_eq!(java.read_byte().unwrap(), -2);
+    assert_eq!(java.read_unsigned_short().unwrap(), 0xfffe);
+    assert_eq!(java.read_char().unwrap(), 'A' as u16);
+    assert_eq!(java.read_int().unwrap(), 0x0102_0304);
+    assert_eq!(java.read_long().unwrap(), -1);
+    assert_eq!(java.read_float().unwrap(), 1.5);
+    assert_eq!(java.read_double().unwrap(), 1.0);
+    assert_eq!(java.read_double().unwrap(), -0.1);
+    let mut chars = [0u8; 4];
+    java.read_fully(&mut chars).unwrap();
+    assert_eq!(chars, [0, b'h', 0, b'i']);
+}
+
+// modified UTF-8：U+0000 写成 C0 80，U+FFFF 以上的字符写成两个三字节的代理项
+#[test]
+fn utf_matches_data_output_stream() {
+    fn t(text: &str, expected: &[u8]) {
+        let mut o = OutputByteStream::default();
+        JavaDataOutput::new(&mut o).write_utf(text).unwrap();
+        assert_eq!(o.buffer(), expected);
+
+        let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+        assert_eq!(JavaDataInput::new(&mut i).read_utf().unwrap(), text);
+        assert!(i.is_empty());
+    }
+
+    t("", &[0x00, 0x00]);
+    t("A", &[0x00, 0x01, 0x41]);
+    t("\0", &[0x00, 0x02, 0xc0, 0x80]);
+    t("é", &[0x00, 0x02, 0xc3, 0xa9]);
+    t("€", &[0x00, 0x03, 0xe2, 0x82, 0xac]);
+    t("😀", &[0x00, 0x06, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]);
+    t("a\0€", &[0x00, 0x06, 0x61, 0xc0, 0x80, 0xe2, 0x82, 0xac]);
+}
+
+#[test]
+fn utf_errors() {
+    let mut o = OutputByteStream::default();
+    assert_eq!(
+        JavaDataOutput::new(&mut o).write_utf(&"€".repeat(21846)),
+        Err(StreamError::LengthTooLarge { len: 65538, max: 65535 })
+    );
+    assert!(o.buffer().is_empty());
+
+    fn read(data: &[u8]) -> Result<String, StreamError> {
+        let mut i = InputByteStream::new(data, Endianness::BigEndian);
+        JavaDataInput::new(&mut i).read_utf()
+    }
+    assert_eq!(read(&[0x00, 0x01, 0x80]), Err(StreamError::InvalidModifiedUtf8 { offset: 2 }));
+    assert_eq!(read(&[0x00, 0x02, 0x41, 0xc3]), Err(StreamError::InvalidModifiedUtf8 { offset: 3 }));
+    assert_eq!(read(&[0x00, 0x02, 0xe2, 0x82]), Err(StreamError::InvalidModifiedUtf8 { offset: 2 }));
+    assert_eq!(read(&[0x00, 0x01, 0xf0]), Err(StreamError::InvalidModifiedUtf8 { offset: 2 }));
+    assert_eq!(read(&[0x00, 0x03, 0xed, 0xa0, 0xbd]), Err(StreamError::InvalidUtf16));
+    assert!(matches!(read(&[0x00, 0x05, 0x41]), Err(StreamError::NotEnoughBytes { .. })));
+}
@@ -0,0 +1,296 @@
+use memory_stream::bit_stream::input_bit_stream::InputBitStream;
+use memory_stream::bit_stream::output_bit_stream::OutputBitStream;
+use memory_stream::byte_stream::Endianness;
+use memory_stream::byte_stream::input_byte_stream::InputByteStream;
+use memory_stream::byte_stream::output_byte_stream::OutputByteStream;
+use memory_stream::byte_stream::fixed_output_byte_stream::FixedOutputByteStream;
+use memory_stream::error::{Result, StreamError};
+use memory_stream::stream::{StreamReader, StreamWriter, StringPadding};
+
+#[derive(Debug, PartialEq)]
+struct Packet {
+    kind: u64,
+    id: u32,
+    delta: i32,
+    position: (f32, f64),
+    guid: u128,
+    alive: bool,
+    name: String,
+}
+
+impl Packet {
+    fn sample() -> Packet {
+        Packet {
+            kind: 5,
+            id: 70000,
+            delta: -3,
+            position: (1.5, -2.25),
+            guid: u128::MAX - 1,
+            alive: true,
+            name: "player".to_string(),
+        }
+    }
+
+    fn serialize<W: StreamWriter>(&self, w: &mut W) -> Result<()> {
+        w.write_bits(self.kind, 3)?;
+        w.write_var_u32(self.id)?;
+        w.write_var_i32(self.delta)?;
+        w.write_f32(self.position.0)?;
+        w.write_f64(self.position.1)?;
+        w.write_u128(self.guid)?;
+        w.write_bool(self.alive)?;
+        w.write_string(&self.name)
+    }
+
+    fn deserialize<R: StreamReader>(r: &mut R) -> Result<Packet> {
+        Ok(Packet {
+            kind: r.read_bits(3)?,
+            id: r.read_var_u32()?,
+            delta: r.read_var_i32()?,
+            position: (r.read_f32()?, r.read_f64()?),
+            guid: r.read_u128()?,
+            alive: r.read_bool()?,
+            name: r.read_string()?,
+        })
+    }
+}
+
+#[test]
+fn serialize_to_byte_stream() {
+    let mut o = OutputByteStream::default();
+    Packet::sample().serialize(&mut o).unwrap();
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(Packet::deserialize(&mut i).unwrap(), Packet::sample());
+}
+
+#[test]
+fn serialize_to_bit_stream() {
+    let mut o = OutputBitStream::default();
+    Packet::sample().serialize(&mut o).unwrap();
+
+    let mut i = InputBitStream::new(o.buffer());
+    assert_eq!(Packet::deserialize(&mut i).unwrap(), Packet::sample());
+}
+
+#[test]
+fn write_bits_on_byte_stream() {
+    fn t(endianness: Endianness, expected: [u8; 3]) {
+        let mut o = OutputByteStream {
+            endianness,
+            ..Default::default()
+        };
+        StreamWriter::write_bits(&mut o, 0x1_0203, 17).unwrap();
+        assert_eq!(o.buffer(), expected);
+
+        let mut i = InputByteStream::new(o.buffer(), endianness);
+        assert_eq!(StreamReader::read_bits(&mut i, 17).unwrap(), 0x1_0203);
+    }
+
+    t(Endianness::BigEndian, [1, 2, 3]);
+    t(Endianness::LittleEndian, [3, 2, 1]);
+}
+
+#[test]
+fn write_bits_on_byte_stream_checks_space() {
+    let mut buffer = [0u8; 2];
+    let mut o = FixedOutputByteStream::new(&mut buffer, Endianness::BigEndian);
+    assert_eq!(
+        StreamWriter::write_bits(&mut o, 0x1_0203, 24),
+        Err(StreamError::NotEnoughSpace { requested: 3, offset: 0, remaining: 2 })
+    );
+    assert!(StreamWriter::write_bits(&mut o, 0x1_0203, 17).is_err());
+    assert_eq!(o.remaining(), 2);
+}
+
+#[test]
+fn read_bits_on_byte_stream_masks_high_bits() {
+    let data = [0xff, 0xff, 0xff];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert_eq!(StreamReader::read_bits(&mut i, 17).unwrap(), 0x1_ffff);
+
+    let mut i = InputByteStream::new(&data, Endianness::LittleEndian);
+    assert_eq!(StreamReader::read_bits(&mut i, 12).unwrap(), 0xfff);
+    assert_eq!(StreamReader::read_signed_bits(&mut i, 4).unwrap(), -1);
+}
+
+fn write_strings<W: StreamWriter>(w: &mut W) -> Result<()> {
+    w.write_string_u8("ab")?;
+    w.write_string_u16("cd")?;
+    w.write_string_var("ef")?;
+    w.write_string_nul("gh")?;
+    w.write_string_fixed("ij", 4, StringPadding::Zero)?;
+    w.write_string_fixed("kl", 4, StringPadding::Space)
+}
+
+fn read_strings<R: StreamReader>(r: &mut R) {
+    assert_eq!(r.read_string_u8().unwrap(), "ab");
+    assert_eq!(r.read_string_u16().unwrap(), "cd");
+    assert_eq!(r.read_string_var().unwrap(), "ef");
+    assert_eq!(r.read_string_nul().unwrap(), "gh");
+    assert_eq!(r.read_string_fixed(4, StringPadding::Zero).unwrap(), "ij");
+    assert_eq!(r.read_string_fixed(4, StringPadding::Space).unwrap(), "kl");
+}
+
+#[test]
+fn string_encodings() {
+    let expected = [
+        2, b'a', b'b',
+        0, 2, b'c', b'd',
+        2, b'e', b'f',
+        b'g', b'h', 0,
+        b'i', b'j', 0, 0,
+        b'k', b'l', b' ', b' ',
+    ];
+
+    let mut o = OutputByteStream::default();
+    write_strings(&mut o).unwrap();
+    assert_eq!(o.buffer(), expected);
+    read_strings(&mut InputByteStream::new(o.buffer(), Endianness::BigEndian));
+
+    let mut o = OutputBitStream::default();
+    o.write_bool(true);
+    write_strings(&mut o).unwrap();
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    read_strings(&mut i);
+}
+
+#[test]
+fn string_encoding_errors() {
+    let mut o = OutputByteStream::default();
+    assert_eq!(
+        o.write_string_u8(&"a".repeat(256)),
+        Err(StreamError::LengthTooLarge { len: 256, max: 255 })
+    );
+    assert_eq!(o.write_string_nul("a\0b"), Err(StreamError::InteriorNul { position: 1 }));
+    assert_eq!(
+        o.write_string_fixed("abcde", 4, StringPadding::Space),
+        Err(StreamError::StringTooLong { len: 5, max: 4 })
+    );
+    assert_eq!(
+        o.write_string_fixed("\0", 4, StringPadding::Zero),
+        Err(StreamError::InteriorNul { position: 0 })
+    );
+    assert!(o.buffer().is_empty());
+
+    // 容量不足时什么都不写
+    let mut buf = [0u8; 4];
+    let mut fixed = FixedOutputByteStream::new(&mut buf, Endianness::BigEndian);
+    assert!(fixed.write_string_nul("abcd").is_err());
+    assert!(fixed.write_string_fixed("a", 5, StringPadding::Zero).is_err());
+    assert_eq!(fixed.remaining(), 4);
+
+    let data = [b'a', b'b'];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert_eq!(i.read_string_nul(), Err(StreamError::MissingNulTerminator { len: 2 }));
+    assert_eq!(i.position(), 0);
+    assert!(matches!(
+        StreamReader::read_string_fixed(&mut i, 3, StringPadding::Zero),
+        Err(StreamError::NotEnoughBytes { .. })
+    ));
+    assert!(matches!(
+        StreamReader::read_string_u8(&mut i),
+        Err(StreamError::NotEnoughBytes { .. })
+    ));
+
+    let mut i = InputBitStream::new(&data);
+    assert_eq!(i.read_string_nul(), Err(StreamError::MissingNulTerminator { len: 2 }));
+    assert_eq!(i.bit_position(), 0);
+}
+
+#[test]
+fn raw_bytes_through_traits() {
+    fn write<W: StreamWriter>(w: &mut W) -> Result<()> {
+        w.write_raw(b"blob")
+    }
+    fn read<R: StreamReader>(r: &mut R) -> [u8; 4] {
+        let mut out = [0u8; 4];
+        r.read_raw(&mut out).unwrap();
+        out
+    }
+
+    let mut o = OutputByteStream::default();
+    write(&mut o).unwrap();
+    assert_eq!(read(&mut InputByteStream::new(o.buffer(), Endianness::BigEndian)), *b"blob");
+
+    let mut o = OutputBitStream::default();
+    write(&mut o).unwrap();
+    assert_eq!(read(&mut InputBitStream::new(o.buffer())), *b"blob");
+}
+
+#[test]
+fn chars() {
+    let chars = ['a', 'é', '€', '😀'];
+
+    let mut o = OutputByteStream::default();
+    for c in chars {
+        o.write_char(c).unwrap();
+    }
+    assert_eq!(o.buffer(), "aé€😀".as_bytes());
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    for c in chars {
+        assert_eq!(i.read_char().unwrap(), c);
+    }
+
+    let mut o = OutputBitStream::default();
+    o.write_bool(true);
+    for c in chars {
+        o.write_char(c).unwrap();
+    }
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(i.read_bool().unwrap());
+    for c in chars {
+        assert_eq!(i.read_char().unwrap(), c);
+    }
+
+    let data = [0xe2, 0x82];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert!(matches!(StreamReader::read_char(&mut i), Err(StreamError::NotEnoughBytes { .. })));
+    let data = [0xff];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert!(matches!(StreamReader::read_char(&mut i), Err(StreamError::InvalidUtf8(_))));
+
+    // 失败时不移动读取位置
+    let data = [0xe2, 0x28, 0xa1];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert!(matches!(StreamReader::read_char(&mut i), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!(i.position(), 0);
+    let mut i = InputBitStream::new(&data);
+    assert!(matches!(i.read_char(), Err(StreamError::InvalidUtf8(_))));
+    assert_eq!(i.bit_position(), 0);
+}
+
+#[test]
+fn utf16_strings() {
+    let mut o = OutputByteStream::default();
+    o.write_string_utf16("Aé€😀", Endianness::LittleEndian).unwrap();
+    o.write_string_utf16("Aé€😀", Endianness::BigEndian).unwrap();
+    assert_eq!(
+        o.buffer(),
+        [
+            0, 0, 0, 5, 0x41, 0, 0xe9, 0, 0xac, 0x20, 0x3d, 0xd8, 0x00, 0xde,
+            0, 0, 0, 5, 0, 0x41, 0, 0xe9, 0x20, 0xac, 0xd8, 0x3d, 0xde, 0x00,
+        ]
+    );
+
+    let mut i = InputByteStream::new(o.buffer(), Endianness::BigEndian);
+    assert_eq!(i.read_string_utf16(Endianness::LittleEndian).unwrap(), "Aé€😀");
+    assert_eq!(i.read_string_utf16(Endianness::BigEndian).unwrap(), "Aé€😀");
+
+    let mut o = OutputBitStream::default();
+    o.write_bool(false);
+    o.write_string_utf16("名前", Endianness::LittleEndian).unwrap();
+    let mut i = InputBitStream::new(o.buffer());
+    assert!(!i.read_bool().unwrap());
+    assert_eq!(i.read_string_utf16(Endianness::LittleEndian).unwrap(), "名前");
+
+    // 不成对的高位代理项
+    let data = [0, 0, 0, 1, 0x3d, 0xd8];
+    let mut i = InputByteStream::new(&data, Endianness::BigEndian);
+    assert_eq!(i.read_string_utf16(Endianness::LittleEndian), Err(StreamError::InvalidUtf16));
+    assert_eq!(i.position(), 0);
+    let mut i = InputBitStream::new(&data);
+    assert_eq!(i.read_string_utf16(Endianness::LittleEndian), Err(StreamError::InvalidUtf16));
+    assert_eq!(i.bit_position(), 0);
+}